@@ -0,0 +1,401 @@
+//! Parse ANSI/VT escape streams into a framebuffer. This is the inverse of
+//! presenting a frame, and can also import ANSI art
+
+use crate::attributes::Attributes;
+use crate::color::Color;
+use crate::csi_color::xterm256_color;
+use crate::framebuffer::Framebuffer;
+use crate::palette::Palette;
+use crate::unicode::{char_width, is_zero_width};
+
+/// Parser state, between bytes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After ESC
+    Escape,
+    /// After ESC and intermediate bytes, such as `ESC (`
+    EscapeIntermediate,
+    /// After `ESC [`
+    Csi,
+    /// After `ESC ]`, until BEL or ST
+    Osc,
+    /// After ESC inside an OSC string
+    OscEscape,
+}
+
+/// A VT parser that draws the text it reads into a framebuffer. Supports
+/// UTF-8 text, the common C0 controls, cursor moves, erasing, scrolling and
+/// SGR attributes and colors (16 color, 256 color and truecolor, in both the
+/// `;` and `:` forms). Anything else is skipped
+///
+/// Cursor moves are clamped to the framebuffer, like a terminal of that size
+/// would do. Presented frames leave the cursor on the line below them, so
+/// parse them with one extra row to keep the last row from being erased
+/// Longest parameter string kept for a control sequence. Real sequences are
+/// far shorter, the rest of a longer one is dropped
+const MAX_PARAMS_LEN: usize = 256;
+
+#[derive(Debug)]
+pub struct AnsiParser {
+    framebuffer: Framebuffer,
+    /// Colors used for the 16 color SGR codes and the first 16 entries of the
+    /// 256 color palette
+    pub palette: Palette,
+    /// Colors shown when the stream selects the default colors
+    pub default_fg: Color,
+    pub default_bg: Color,
+    state: State,
+    params: String,
+    private: bool,
+    utf8: Vec<u8>,
+    cursor: (usize, usize),
+    saved_cursor: (usize, usize),
+    /// The cursor is past the last column, and wraps on the next character
+    wrap_pending: bool,
+    fg: Color,
+    bg: Color,
+    attributes: Attributes,
+}
+
+impl AnsiParser {
+    /// Create a parser drawing into a blank framebuffer of the given size
+    pub fn new(width: usize, height: usize) -> AnsiParser {
+        let palette = Palette::default();
+        let (default_fg, default_bg) = (palette.colors[7], palette.colors[0]);
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.clear(' ', default_fg, default_bg);
+        
+        AnsiParser{
+            framebuffer,
+            palette,
+            default_fg,
+            default_bg,
+            state: State::Ground,
+            params: String::new(),
+            private: false,
+            utf8: Vec::new(),
+            cursor: (0, 0),
+            saved_cursor: (0, 0),
+            wrap_pending: false,
+            fg: default_fg,
+            bg: default_bg,
+            attributes: Attributes::NONE,
+        }
+    }
+    
+    /// Get the framebuffer drawn so far
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+    
+    /// Stop parsing and take the framebuffer
+    pub fn into_framebuffer(self) -> Framebuffer {
+        self.framebuffer
+    }
+    
+    /// Get the cursor position, as (column, row) from 0
+    pub fn cursor(&self) -> (usize, usize) {
+        self.cursor
+    }
+    
+    /// Parse more of the stream. Escape sequences and UTF-8 characters may be
+    /// split between calls
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.byte(byte);
+        }
+    }
+    
+    fn byte(&mut self, byte: u8) {
+        match self.state {
+            State::Ground => self.ground(byte),
+            State::Escape => {
+                self.state = State::Ground;
+                match byte {
+                    b'[' => {
+                        self.params.clear();
+                        self.private = false;
+                        self.state = State::Csi;
+                    },
+                    b']' => self.state = State::Osc,
+                    b'7' => self.saved_cursor = self.cursor,
+                    b'8' => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+                    0x20..=0x2F => self.state = State::EscapeIntermediate,
+                    _ => {},
+                }
+            },
+            State::EscapeIntermediate => {
+                if !(0x20..=0x2F).contains(&byte) {
+                    self.state = State::Ground;
+                }
+            },
+            State::Csi => match byte {
+                b'0'..=b'9' | b';' | b':' => {
+                    if self.params.len() < MAX_PARAMS_LEN {
+                        self.params.push(byte as char);
+                    }
+                },
+                b'?' | b'>' | b'=' | b'<' => self.private = true,
+                // Intermediate bytes aren't used by any supported sequence
+                0x20..=0x2F => {},
+                0x40..=0x7E => {
+                    self.state = State::Ground;
+                    if !self.private {
+                        self.csi(byte);
+                    }
+                },
+                // Sequences can't contain other bytes; abort
+                _ => self.state = State::Ground,
+            },
+            State::Osc => match byte {
+                0x07 => self.state = State::Ground,
+                0x1B => self.state = State::OscEscape,
+                _ => {},
+            },
+            State::OscEscape => self.state = if byte == b'\\' { State::Ground } else { State::Osc },
+        }
+    }
+    
+    fn ground(&mut self, byte: u8) {
+        if byte < 0x80 {
+            // An unfinished UTF-8 character is invalid
+            if !self.utf8.is_empty() {
+                self.utf8.clear();
+                self.print('\u{FFFD}');
+            }
+            
+            match byte {
+                0x1B => self.state = State::Escape,
+                b'\r' => self.move_to(0, self.cursor.1),
+                b'\n' | 0x0B | 0x0C => self.line_feed(),
+                0x08 => self.move_to(self.cursor.0.saturating_sub(1), self.cursor.1),
+                b'\t' => self.move_to((self.cursor.0 / 8 + 1) * 8, self.cursor.1),
+                // Other controls, such as BEL, XON and XOFF, don't draw
+                0x00..=0x1F | 0x7F => {},
+                _ => self.print(byte as char),
+            }
+            return;
+        }
+        
+        // Collect UTF-8 continuation bytes until the character is complete
+        if byte & 0xC0 != 0x80 && !self.utf8.is_empty() {
+            self.utf8.clear();
+            self.print('\u{FFFD}');
+        }
+        self.utf8.push(byte);
+        let expected = match self.utf8[0] {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        if self.utf8.len() >= expected {
+            let c = std::str::from_utf8(&self.utf8).ok().and_then(|text| text.chars().next()).unwrap_or('\u{FFFD}');
+            self.utf8.clear();
+            self.print(c);
+        }
+    }
+    
+    /// Draw a character at the cursor and advance it
+    fn print(&mut self, c: char) {
+        let (width, height) = (self.framebuffer.width, self.framebuffer.height);
+        
+        // Zero width characters attach to the character before the cursor
+        if is_zero_width(c) {
+            let x = if self.wrap_pending { self.cursor.0 } else { self.cursor.0.saturating_sub(1) };
+            self.framebuffer.set_cell(x, self.cursor.1, Some(c), None, None);
+            return;
+        }
+        
+        let c_width = char_width(c).max(1);
+        if self.wrap_pending || (self.cursor.0 + c_width > width && self.cursor.0 < width) {
+            self.move_to(0, self.cursor.1);
+            self.line_feed();
+        }
+        
+        let (x, y) = self.cursor;
+        if y < height {
+            self.framebuffer.set_styled_cell(x, y, Some(c), Some(self.fg), Some(self.bg), Some(self.attributes));
+        }
+        
+        // The cursor stays on the last column until the next character
+        if x + c_width >= width && x < width {
+            self.cursor.0 = width - 1;
+            self.wrap_pending = true;
+        }
+        else {
+            self.cursor.0 = x + c_width;
+        }
+    }
+    
+    /// Move the cursor, clamped to the framebuffer
+    fn move_to(&mut self, x: usize, y: usize) {
+        self.cursor = (x.min(self.framebuffer.width.saturating_sub(1)), y.min(self.framebuffer.height.saturating_sub(1)));
+        self.wrap_pending = false;
+    }
+    
+    /// Move down a line, scrolling up at the bottom of the framebuffer
+    fn line_feed(&mut self) {
+        let height = self.framebuffer.height;
+        if self.cursor.1 + 1 >= height {
+            self.framebuffer.scroll_up(self.default_fg, self.bg);
+            self.move_to(self.cursor.0, height.saturating_sub(1));
+        }
+        else {
+            self.move_to(self.cursor.0, self.cursor.1 + 1);
+        }
+    }
+    
+    /// Erase cells of a row, from `start` to `end` (exclusive), to blanks with
+    /// the current background color
+    fn erase(&mut self, y: usize, start: usize, end: usize) {
+        for x in start..end.min(self.framebuffer.width) {
+            self.framebuffer.set_styled_cell(x, y, Some(' '), Some(self.default_fg), Some(self.bg), Some(Attributes::NONE));
+        }
+    }
+    
+    fn csi(&mut self, command: u8) {
+        // Parameters are separated by `;`, and may have `:` separated
+        // sub-parameters, such as `38:2::r:g:b`
+        let params: Vec<Vec<usize>> = self.params.split(';').map(|param| {
+            // Only digits are kept, so a parse error means an overflow
+            param.split(':').map(|sub| {
+                if sub.is_empty() { 0 } else { sub.parse().unwrap_or(usize::MAX) }
+            }).collect()
+        }).collect();
+        let param = |index: usize| params.get(index).map_or(0, |param| param[0]);
+        // Missing and zero parameters count as 1 for moves
+        let count = param(0).max(1);
+        let (x, y) = self.cursor;
+        let (width, height) = (self.framebuffer.width, self.framebuffer.height);
+        
+        match command {
+            b'H' | b'f' => self.move_to(param(1).max(1) - 1, count - 1),
+            b'A' => self.move_to(x, y.saturating_sub(count)),
+            b'B' => self.move_to(x, y.saturating_add(count)),
+            b'C' => self.move_to(x.saturating_add(count), y),
+            b'D' => self.move_to(x.saturating_sub(count), y),
+            b'E' => self.move_to(0, y.saturating_add(count)),
+            b'F' => self.move_to(0, y.saturating_sub(count)),
+            b'G' => self.move_to(count - 1, y),
+            b'd' => self.move_to(x, count - 1),
+            b'J' => {
+                let rows = match param(0) {
+                    0 => {
+                        self.erase(y, x, width);
+                        y + 1..height
+                    },
+                    1 => {
+                        self.erase(y, 0, x + 1);
+                        0..y.min(height)
+                    },
+                    _ => 0..height,
+                };
+                for row in rows {
+                    self.erase(row, 0, width);
+                }
+            },
+            b'K' => match param(0) {
+                0 => self.erase(y, x, width),
+                1 => self.erase(y, 0, x + 1),
+                _ => self.erase(y, 0, width),
+            },
+            b'S' => {
+                // Scrolling more than the height blanks everything anyway
+                for _ in 0..count.min(height) {
+                    self.framebuffer.scroll_up(self.default_fg, self.bg);
+                }
+            },
+            b's' => self.saved_cursor = self.cursor,
+            b'u' => self.move_to(self.saved_cursor.0, self.saved_cursor.1),
+            b'm' => self.sgr(&params),
+            _ => {},
+        }
+    }
+    
+    /// Get a 256 color palette entry. The first 16 come from the palette
+    fn indexed_color(&self, index: usize) -> Color {
+        if index < 16 {
+            self.palette.colors[index]
+        }
+        else {
+            xterm256_color(index.min(255) as u8)
+        }
+    }
+    
+    /// Get the color of a `38`/`48` parameter with `:` sub-parameters, like
+    /// `38:5:n`, `38:2::r:g:b` or the older `38:2:r:g:b`
+    fn sub_param_color(&self, sub: &[usize]) -> Option<Color> {
+        let channels = |rgb: &[usize]| Color{r: rgb[0].min(255) as u8, g: rgb[1].min(255) as u8, b: rgb[2].min(255) as u8};
+        match (sub.first(), sub.len()) {
+            (Some(5), 2..) => Some(self.indexed_color(sub[1])),
+            // The first sub-parameter after 2 is a color space ID, usually empty
+            (Some(2), 5..) => Some(channels(&sub[2..5])),
+            (Some(2), 4) => Some(channels(&sub[1..4])),
+            _ => None,
+        }
+    }
+    
+    fn sgr(&mut self, params: &[Vec<usize>]) {
+        let mut params = params.iter();
+        while let Some(group) = params.next() {
+            let (param, sub) = (group[0], &group[1..]);
+            match param {
+                0 => {
+                    self.fg = self.default_fg;
+                    self.bg = self.default_bg;
+                    self.attributes = Attributes::NONE;
+                },
+                22 => self.attributes.remove(Attributes::BOLD | Attributes::DIM),
+                30..=37 => self.fg = self.palette.colors[param - 30],
+                90..=97 => self.fg = self.palette.colors[param - 90 + 8],
+                40..=47 => self.bg = self.palette.colors[param - 40],
+                100..=107 => self.bg = self.palette.colors[param - 100 + 8],
+                39 => self.fg = self.default_fg,
+                49 => self.bg = self.default_bg,
+                38 | 48 => {
+                    let mut next = || params.next().map_or(0, |group| group[0]);
+                    let color = if !sub.is_empty() {
+                        self.sub_param_color(sub)
+                    }
+                    else {
+                        match next() {
+                            5 => Some(self.indexed_color(next())),
+                            2 => {
+                                let mut channel = || next().min(255) as u8;
+                                Some(Color{r: channel(), g: channel(), b: channel()})
+                            },
+                            _ => None,
+                        }
+                    };
+                    if let Some(color) = color {
+                        if param == 38 { self.fg = color } else { self.bg = color }
+                    }
+                },
+                // Underline styles, such as `4:3` for curly; `4:0` turns it off
+                4 if sub.first() == Some(&0) => self.attributes.remove(Attributes::UNDERLINE),
+                _ => {
+                    for &(attribute, on, off) in &Attributes::SGR_CODES {
+                        if param == on as usize {
+                            self.attributes.insert(attribute);
+                        }
+                        else if param == off as usize {
+                            self.attributes.remove(attribute);
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Create a framebuffer from an ANSI/VT escape stream, such as a frame
+    /// returned by `get_string` or ANSI art. See `AnsiParser`
+    pub fn from_ansi(width: usize, height: usize, stream: &[u8]) -> Framebuffer {
+        let mut parser = AnsiParser::new(width, height);
+        parser.feed(stream);
+        parser.into_framebuffer()
+    }
+}
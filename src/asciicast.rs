@@ -0,0 +1,449 @@
+//! Recording and reading of asciicast v2 files, the format used by asciinema
+
+use crate::framebuffer::{Framebuffer, PresentError};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Records output to an asciicast v2 file: a JSON header line followed by
+/// one JSON line per event, timed from when the recorder was created
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    out: W,
+    start: Instant,
+    /// Reused by `present` for each frame
+    frame: Vec<u8>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording of a terminal of the given size in cells, writing
+    /// the header
+    pub fn new(mut out: W, width: usize, height: usize) -> io::Result<Recorder<W>> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        writeln!(out, "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}}}", width, height, timestamp)?;
+        Ok(Recorder{
+            out,
+            start: Instant::now(),
+            frame: Vec::new(),
+        })
+    }
+    
+    /// Write an event with the current time
+    fn event(&mut self, kind: &str, data: &str) -> io::Result<()> {
+        let mut line = format!("[{:.6}, \"{}\", ", self.start.elapsed().as_secs_f64(), kind);
+        push_json_string(&mut line, data);
+        line.push_str("]\n");
+        self.out.write_all(line.as_bytes())
+    }
+    
+    /// Record output sent to the terminal
+    pub fn output(&mut self, data: &str) -> io::Result<()> {
+        self.event("o", data)
+    }
+    
+    /// Record a terminal resize
+    pub fn resize(&mut self, width: usize, height: usize) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", width, height))
+    }
+    
+    /// Present a frame to `out`, like `Framebuffer::present`, and record it
+    pub fn present(&mut self, framebuffer: &mut Framebuffer, out: &mut impl Write) -> Result<(), PresentError> {
+        let mut frame = std::mem::take(&mut self.frame);
+        frame.clear();
+        framebuffer.present(&mut frame)?;
+        out.write_all(&frame)?;
+        out.flush()?;
+        let result = self.output(&String::from_utf8_lossy(&frame));
+        self.frame = frame;
+        Ok(result?)
+    }
+    
+    /// Flush the recording
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Append a string as a JSON string literal
+fn push_json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c as u32 == 0x7F => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// An asciicast event
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    /// Seconds since the start of the recording
+    pub time: f64,
+    /// Event type: `o` for output, `i` for input, `r` for resize, `m` for
+    /// marker
+    pub kind: String,
+    pub data: String,
+}
+
+/// A parsed asciicast v2 file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cast {
+    pub width: usize,
+    pub height: usize,
+    pub events: Vec<Event>,
+}
+
+impl Cast {
+    /// Read an asciicast v2 file
+    pub fn read(input: impl BufRead) -> Result<Cast, CastError> {
+        let mut lines = input.lines().enumerate();
+        let header_line = match lines.next() {
+            Some((_, line)) => line?,
+            None => return Err(CastError::Parse{line: 1, message: "missing header".to_string()}),
+        };
+        
+        let error = |line: usize, message: &str| CastError::Parse{line, message: message.to_string()};
+        let header = Json::parse(&header_line).ok_or_else(|| error(1, "header is not valid JSON"))?;
+        if header.get("version").and_then(Json::as_number) != Some(2.0) {
+            return Err(error(1, "only asciicast version 2 is supported"));
+        }
+        let size = |key| header.get(key).and_then(Json::as_number).ok_or_else(|| error(1, &format!("header is missing `{}`", key)));
+        let (width, height) = (size("width")? as usize, size("height")? as usize);
+        
+        let mut events = Vec::new();
+        for (line_index, line) in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            
+            let error = |message| error(line_index + 1, message);
+            let event = match Json::parse(&line).ok_or_else(|| error("event is not valid JSON"))? {
+                Json::Array(event) => event,
+                _ => return Err(error("event is not an array")),
+            };
+            match event.as_slice() {
+                [Json::Number(time), Json::String(kind), Json::String(data)] => events.push(Event{
+                    time: *time,
+                    kind: kind.clone(),
+                    data: data.clone(),
+                }),
+                _ => return Err(error("expected `[time, type, data]`")),
+            }
+        }
+        
+        Ok(Cast{width, height, events})
+    }
+}
+
+/// Error returned when reading an asciicast file
+#[derive(Debug)]
+pub enum CastError {
+    Io(io::Error),
+    /// Malformed file. `line` starts at 1
+    Parse{line: usize, message: String},
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CastError::Io(error) => write!(f, "could not read cast: {}", error),
+            CastError::Parse{line, message} => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for CastError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CastError::Io(error) => Some(error),
+            CastError::Parse{..} => None,
+        }
+    }
+}
+
+impl From<io::Error> for CastError {
+    fn from(error: io::Error) -> CastError {
+        CastError::Io(error)
+    }
+}
+
+/// Just enough JSON to read asciicast files
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parse a JSON document, or return `None` if it is invalid
+    fn parse(text: &str) -> Option<Json> {
+        let mut parser = JsonParser{text: text.as_bytes(), pos: 0, depth: 0};
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos == text.len() { Some(value) } else { None }
+    }
+    
+    /// Get a member of an object
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    
+    fn as_number(&self) -> Option<f64> {
+        match *self {
+            Json::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+}
+
+/// Deepest nesting of arrays and objects accepted, so that malicious files
+/// can't overflow the stack
+const MAX_JSON_DEPTH: usize = 64;
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    /// Number of arrays and objects the parser is inside
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && b" \t\r\n".contains(&self.text[self.pos]) {
+            self.pos += 1;
+        }
+    }
+    
+    /// Skip whitespace and consume a byte if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        if self.text.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        }
+        else {
+            false
+        }
+    }
+    
+    fn literal(&mut self, literal: &str, value: Json) -> Option<Json> {
+        if self.text[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Some(value)
+        }
+        else {
+            None
+        }
+    }
+    
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match *self.text.get(self.pos)? {
+            b'n' => self.literal("null", Json::Null),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            open @ b'[' | open @ b'{' => {
+                if self.depth == MAX_JSON_DEPTH {
+                    return None;
+                }
+                self.pos += 1;
+                self.depth += 1;
+                let value = if open == b'[' { self.array() } else { self.object() };
+                self.depth -= 1;
+                value
+            },
+            _ => {
+                let start = self.pos;
+                while self.pos < self.text.len() && b"+-.eE0123456789".contains(&self.text[self.pos]) {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.text[start..self.pos]).ok()?.parse().ok().map(Json::Number)
+            },
+        }
+    }
+    
+    /// Parse the rest of an array, after its opening bracket
+    fn array(&mut self) -> Option<Json> {
+        let mut items = Vec::new();
+        if self.eat(b']') {
+            return Some(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(b']') {
+                return Some(Json::Array(items));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+    
+    /// Parse the rest of an object, after its opening brace
+    fn object(&mut self) -> Option<Json> {
+        let mut members = Vec::new();
+        if self.eat(b'}') {
+            return Some(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            if !self.eat(b':') {
+                return None;
+            }
+            members.push((key, self.value()?));
+            if self.eat(b'}') {
+                return Some(Json::Object(members));
+            }
+            if !self.eat(b',') {
+                return None;
+            }
+        }
+    }
+    
+    /// Parse a string literal, starting at its opening quote
+    fn string(&mut self) -> Option<String> {
+        if self.text.get(self.pos) != Some(&b'"') {
+            return None;
+        }
+        self.pos += 1;
+        
+        let mut out = Vec::new();
+        loop {
+            let byte = *self.text.get(self.pos)?;
+            self.pos += 1;
+            match byte {
+                b'"' => return String::from_utf8(out).ok(),
+                b'\\' => {
+                    let escape = *self.text.get(self.pos)?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // Characters outside the BMP are surrogate pairs
+                            if (0xD800..0xDC00).contains(&code) && self.text[self.pos..].starts_with(b"\\u") {
+                                let start = self.pos;
+                                self.pos += 2;
+                                match self.hex4()? {
+                                    low @ 0xDC00..=0xDFFF => code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00),
+                                    // Not a pair, so the second escape stands on its own
+                                    _ => self.pos = start,
+                                }
+                            }
+                            // Unpaired surrogates aren't characters
+                            char::from_u32(code).unwrap_or('\u{FFFD}')
+                        },
+                        _ => return None,
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                byte => out.push(byte),
+            }
+        }
+    }
+    
+    /// Parse the 4 hex digits of a `\u` escape
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = self.text.get(self.pos..self.pos + 4)?;
+        // `from_str_radix` would also take a sign
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+        self.pos += 4;
+        digits.iter().try_fold(0, |code, &digit| Some(code << 4 | (digit as char).to_digit(16)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_JSON_DEPTH)).is_some());
+        assert_eq!(Json::parse(&nested(MAX_JSON_DEPTH + 1)), None);
+        
+        // Deep enough to overflow the stack without the limit
+        let header = "{\"version\": 2, \"width\": 80, \"height\": 24}";
+        let cast = format!("{}\n{}\n", header, nested(1_000_000));
+        match Cast::read(cast.as_bytes()) {
+            Err(CastError::Parse{line: 2, ..}) => {},
+            result => panic!("expected a parse error on line 2, got {:?}", result),
+        }
+    }
+    
+    fn string(json: &str) -> Option<String> {
+        match Json::parse(json)? {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+    
+    #[test]
+    fn unicode_escapes() {
+        assert_eq!(string(r#""\u00e9\u754C""#).as_deref(), Some("\u{e9}\u{754c}"));
+        assert_eq!(string(r#""\ud83d\ude00""#).as_deref(), Some("\u{1f600}"));
+        // Unpaired surrogates
+        assert_eq!(string(r#""\ud83d\u0041""#).as_deref(), Some("\u{fffd}A"));
+        assert_eq!(string(r#""\ud83d\ud83d\ude00""#).as_deref(), Some("\u{fffd}\u{1f600}"));
+        assert_eq!(string(r#""\ude00x""#).as_deref(), Some("\u{fffd}x"));
+        // Exactly 4 hex digits
+        assert_eq!(string(r#""\u+041""#), None);
+        assert_eq!(string(r#""\u-041""#), None);
+        assert_eq!(string(r#""\u 041""#), None);
+        assert_eq!(string(r#""\u04""#), None);
+        assert_eq!(string(r#""\ud83d\u+e00""#), None);
+    }
+    
+    #[test]
+    fn round_trip() {
+        let mut recorder = Recorder::new(Vec::new(), 5, 2).unwrap();
+        let mut framebuffer = Framebuffer::new(5, 2);
+        let mut terminal = Vec::new();
+        framebuffer.set_cell(0, 0, Some('"'), None, None);
+        framebuffer.set_cell(1, 1, Some('\u{754c}'), None, None);
+        recorder.present(&mut framebuffer, &mut terminal).unwrap();
+        let first = terminal.len();
+        framebuffer.set_cell(4, 0, Some('\\'), None, None);
+        recorder.present(&mut framebuffer, &mut terminal).unwrap();
+        recorder.resize(8, 3).unwrap();
+        
+        let cast = Cast::read(recorder.out.as_slice()).unwrap();
+        assert_eq!((cast.width, cast.height), (5, 2));
+        let kinds: Vec<&str> = cast.events.iter().map(|event| event.kind.as_str()).collect();
+        assert_eq!(kinds, ["o", "o", "r"]);
+        assert!(cast.events[0].time >= 0.0);
+        assert!(cast.events.windows(2).all(|pair| pair[0].time <= pair[1].time));
+        
+        let terminal = String::from_utf8(terminal).unwrap();
+        assert_eq!(cast.events[0].data, terminal[..first]);
+        assert_eq!(cast.events[1].data, terminal[first..]);
+        assert_eq!(cast.events[2].data, "8x3");
+    }
+}
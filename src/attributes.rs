@@ -0,0 +1,67 @@
+use std::ops::{BitOr, BitOrAssign};
+
+/// SGR text attributes of a cell, as a bitset. Combine them with `|`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Attributes(u8);
+
+impl Attributes {
+    pub const NONE: Attributes = Attributes(0);
+    pub const BOLD: Attributes = Attributes(1 << 0);
+    pub const DIM: Attributes = Attributes(1 << 1);
+    pub const ITALIC: Attributes = Attributes(1 << 2);
+    pub const UNDERLINE: Attributes = Attributes(1 << 3);
+    pub const BLINK: Attributes = Attributes(1 << 4);
+    pub const REVERSE: Attributes = Attributes(1 << 5);
+    pub const STRIKETHROUGH: Attributes = Attributes(1 << 6);
+    
+    /// Every attribute with its SGR codes to turn it on and off. Bold and dim
+    /// share their off code
+    pub(crate) const SGR_CODES: [(Attributes, u8, u8); 7] = [
+        (Attributes::BOLD, 1, 22),
+        (Attributes::DIM, 2, 22),
+        (Attributes::ITALIC, 3, 23),
+        (Attributes::UNDERLINE, 4, 24),
+        (Attributes::BLINK, 5, 25),
+        (Attributes::REVERSE, 7, 27),
+        (Attributes::STRIKETHROUGH, 9, 29),
+    ];
+    
+    /// Check whether all attributes of `other` are set
+    pub fn contains(&self, other: Attributes) -> bool {
+        self.0 & other.0 == other.0
+    }
+    
+    /// Check whether any attribute of `other` is set
+    pub fn intersects(&self, other: Attributes) -> bool {
+        self.0 & other.0 != 0
+    }
+    
+    /// Check whether no attributes are set
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+    
+    /// Set the attributes of `other`
+    pub fn insert(&mut self, other: Attributes) {
+        self.0 |= other.0;
+    }
+    
+    /// Unset the attributes of `other`
+    pub fn remove(&mut self, other: Attributes) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for Attributes {
+    type Output = Attributes;
+    
+    fn bitor(self, other: Attributes) -> Attributes {
+        Attributes(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Attributes {
+    fn bitor_assign(&mut self, other: Attributes) {
+        self.0 |= other.0;
+    }
+}
@@ -0,0 +1,58 @@
+use console_render::asciicast::Cast;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: replay <file.cast> [speed]");
+            std::process::exit(2);
+        },
+    };
+    
+    // Speed multiplier, 2 plays twice as fast
+    let speed = match args.next().map(|speed| speed.parse::<f64>()) {
+        None => 1.0,
+        Some(Ok(speed)) if speed > 0.0 && speed.is_finite() => speed,
+        Some(_) => {
+            eprintln!("speed must be a positive number");
+            std::process::exit(2);
+        },
+    };
+    
+    let cast = match File::open(&path).map_err(|error| error.into()).and_then(|file| Cast::read(BufReader::new(file))) {
+        Ok(cast) => cast,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            std::process::exit(1);
+        },
+    };
+    
+    // Work out when each event is due before playing, so that huge times or
+    // tiny speeds are rejected instead of stopping playback halfway
+    let output = cast.events.iter().filter(|event| event.kind == "o");
+    let events = match output.map(|event| Duration::try_from_secs_f64(event.time.max(0.0) / speed).map(|due| (due, event))).collect::<Result<Vec<_>, _>>() {
+        Ok(events) => events,
+        Err(_) => {
+            eprintln!("{}: event times are too large to play at this speed", path);
+            std::process::exit(1);
+        },
+    };
+    
+    // Events are timed from the start, so time spent writing doesn't add up
+    let mut stdout = io::stdout();
+    let start = Instant::now();
+    for (due, event) in events {
+        if let Some(delay) = due.checked_sub(start.elapsed()) {
+            thread::sleep(delay);
+        }
+        
+        if stdout.write_all(event.data.as_bytes()).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use console_render::asciicast::Recorder;
+use console_render::framebuffer::Framebuffer;
+use console_render::color::Color;
+use console_render::geometry::{Line, Point};
+use console_render::palette::Palette;
+use console_render::csi_color::ColorMode;
+use console_render::terminal::{self, Capabilities, GlyphSet};
+use console_render::texture::{Texture, TextureCell, TextureRegistry};
+use console_render::world::{Wall, WallShading, World};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+fn texture_cell_generator(x:usize, y:usize) -> TextureCell {
+    if y == 0 || y == 3 || (y < 3 && x == 5) || (y > 3 && x == 2) {
+        return TextureCell{character: None, fg_color: None, bg_color: Some(Color{r: 100, g: 100, b: 100})};
+    }
+    
+    TextureCell{character: None, fg_color: None, bg_color: Some(Color{r: 255, g: 0, b: 0})}
+}
+
+/// Rows below the frame: the prompt's two lines, and the line the cursor
+/// moves to when Enter is pressed
+const PROMPT_ROWS: usize = 3;
+
+/// Get the framebuffer size that fits the terminal, leaving room for the
+/// prompt below the frame. Pressing Enter then doesn't scroll the terminal,
+/// so the frame stays in place and the next one can be sent as differences
+fn framebuffer_size() -> (usize, usize) {
+    match terminal::size() {
+        Some((columns, rows)) => (columns, rows.saturating_sub(PROMPT_ROWS).max(1)),
+        None => (128, 48),
+    }
+}
+
+fn main() {
+    // Pick color mode and glyphs supported by the terminal
+    let capabilities = Capabilities::detect();
+    
+    // Fill the terminal, and follow it when it is resized
+    terminal::watch_resize();
+    let (width, height) = framebuffer_size();
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.set_color_mode(capabilities.color_mode);
+    
+    // Draw at double vertical resolution with half blocks if they can be shown
+    framebuffer.set_half_block(capabilities.glyphs == GlyphSet::Unicode && capabilities.color_mode != ColorMode::Monochrome);
+    
+    // Optional `--record <file>` argument to record the session as an
+    // asciicast, and palette argument, either a preset name or a theme file
+    let mut args = std::env::args().skip(1);
+    let mut record_path = None;
+    let mut palette_arg = None;
+    while let Some(arg) = args.next() {
+        if arg == "--record" {
+            record_path = args.next();
+        }
+        else {
+            palette_arg = Some(arg);
+        }
+    }
+    
+    let mut recorder = record_path.map(|path| {
+        match File::create(&path).and_then(|file| Recorder::new(BufWriter::new(file), width, height + PROMPT_ROWS)) {
+            Ok(recorder) => recorder,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            },
+        }
+    });
+    
+    if let Some(palette_arg) = palette_arg {
+        let palette = match Palette::preset(&palette_arg) {
+            Some(palette) => Ok(palette),
+            None => Palette::from_file(&palette_arg),
+        };
+        match palette {
+            Ok(palette) => framebuffer.set_palette(palette),
+            Err(error) => {
+                eprintln!("{}: {}", palette_arg, error);
+                std::process::exit(1);
+            },
+        }
+    }
+    
+    // Create world
+    let mut textures = TextureRegistry::new();
+    let bricks = textures.add(Texture::from_generator(6, 6, Point{x: 6.0, y: 6.0}, &texture_cell_generator));
+    let mut world = World{
+        framebuffer,
+        glyphs: capabilities.glyphs,
+        textures,
+        shading: WallShading::Glyphs,
+        walls: vec![
+            Wall{
+                line: Line{
+                    start: Point{x: -10.0, y: 10.0},
+                    end: Point{x: 10.0, y: 10.0},
+                },
+                texture: bricks,
+                height: Wall::DEFAULT_HEIGHT,
+            },
+            Wall{
+                line: Line{
+                    start: Point{x: 10.0, y: 10.0},
+                    end: Point{x: 10.0, y: -10.0},
+                },
+                texture: bricks,
+                height: Wall::DEFAULT_HEIGHT,
+            },
+            Wall{
+                line: Line{
+                    start: Point{x: 10.0, y: -10.0},
+                    end: Point{x: -10.0, y: -10.0},
+                },
+                texture: bricks,
+                height: Wall::DEFAULT_HEIGHT,
+            },
+            Wall{
+                line: Line{
+                    start: Point{x: -10.0, y: -10.0},
+                    end: Point{x: -10.0, y: 10.0},
+                },
+                texture: bricks,
+                height: Wall::DEFAULT_HEIGHT,
+            },
+        ],
+        pos: Point{x: 0.0, y: 0.0},
+        cam_rot: 0.0,
+        cam_hfov: PI * 0.5,
+        cam_range: 30.0,
+    };
+    
+    let mut running = true;
+    while running {
+        if terminal::resized() {
+            let (width, height) = framebuffer_size();
+            world.framebuffer.resize(width, height);
+            if let Some(recorder) = recorder.as_mut() {
+                // Recording is best effort, it never stops the game
+                let _ = recorder.resize(width, height + PROMPT_ROWS);
+            }
+        }
+        
+        world.render();
+        let mut stdout = io::stdout();
+        let presented = match recorder.as_mut() {
+            Some(recorder) => recorder.present(&mut world.framebuffer, &mut stdout),
+            None => world.framebuffer.present(&mut stdout),
+        };
+        if let Err(error) = presented {
+            eprintln!("{}", error);
+            break;
+        }
+        
+        let prompt = "WASD: move; QE: rotate camera; X: quit; Enter: next frame\n> ";
+        if write!(stdout, "{}", prompt).and_then(|_| stdout.flush()).is_err() {
+            break;
+        }
+        if let Some(recorder) = recorder.as_mut() {
+            // The terminal turns newlines into CRLF, record what it receives
+            let _ = recorder.output(&prompt.replace('\n', "\r\n"));
+        }
+        
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(_) => {
+                for c in input.chars() {
+                    let lower_c = c.to_lowercase().next().unwrap();
+                    if lower_c == 'w' {
+                        world.pos = world.pos + Point::from_normal(world.cam_rot, 1.0);
+                    }
+                    else if lower_c == 's' {
+                        world.pos = world.pos - Point::from_normal(world.cam_rot, 1.0);
+                    }
+                    else if lower_c == 'd' {
+                        world.pos = world.pos + Point::from_normal(world.cam_rot + PI * 0.5, 1.0);
+                    }
+                    else if lower_c == 'a' {
+                        world.pos = world.pos - Point::from_normal(world.cam_rot + PI * 0.5, 1.0);
+                    }
+                    else if lower_c == 'q' {
+                        world.cam_rot -= PI / 32.0;
+                    }
+                    else if lower_c == 'e' {
+                        world.cam_rot += PI / 32.0;
+                    }
+                    else if lower_c == 'x' {
+                        running = false;
+                    }
+                }
+            }
+            Err(_) => running = false,
+        }
+    }
+    
+    if let Some(mut recorder) = recorder {
+        if let Err(error) = recorder.flush() {
+            eprintln!("could not save recording: {}", error);
+        }
+    }
+}
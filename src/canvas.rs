@@ -0,0 +1,424 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::geometry::{Line, Point};
+
+/// Quadrant block glyphs, indexed by a bitmask of the filled quadrants
+/// (1: top left, 2: top right, 4: bottom left, 8: bottom right)
+const QUADRANTS: [char; 16] = [
+    ' ', '\u{2598}', '\u{259D}', '\u{2580}',
+    '\u{2596}', '\u{258C}', '\u{259E}', '\u{259B}',
+    '\u{2597}', '\u{259A}', '\u{2590}', '\u{259C}',
+    '\u{2584}', '\u{2599}', '\u{259F}', '\u{2588}',
+];
+
+/// Braille dot bits, indexed by [row][column] inside a cell
+const BRAILLE_DOTS: [[u8; 2]; 4] = [
+    [0x01, 0x08],
+    [0x02, 0x10],
+    [0x04, 0x20],
+    [0x40, 0x80],
+];
+
+/// How a canvas splits each cell into sub-pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CanvasKind {
+    /// 2x4 pixels per cell, drawn with Braille patterns (U+2800 block)
+    Braille,
+    /// 2x2 pixels per cell, drawn with quadrant blocks (U+2596 to U+259F)
+    Quadrant,
+}
+
+impl CanvasKind {
+    /// Get the amount of pixels per cell, horizontally and vertically
+    pub fn cell_size(&self) -> (usize, usize) {
+        match self {
+            CanvasKind::Braille => (2, 4),
+            CanvasKind::Quadrant => (2, 2),
+        }
+    }
+    
+    /// Get the bit of a pixel inside its cell's bitmask
+    fn bit(&self, x: usize, y: usize) -> u8 {
+        match self {
+            CanvasKind::Braille => BRAILLE_DOTS[y][x],
+            CanvasKind::Quadrant => 1 << (y * 2 + x),
+        }
+    }
+    
+    /// Get the glyph for a cell bitmask
+    fn glyph(&self, bits: u8) -> char {
+        match self {
+            CanvasKind::Braille => std::char::from_u32(0x2800 + bits as u32).unwrap(),
+            CanvasKind::Quadrant => QUADRANTS[bits as usize],
+        }
+    }
+}
+
+/// A monochrome sub-cell canvas for line drawing, such as minimaps and debug
+/// overlays. Each cell has a foreground color, set by the last pixel drawn
+/// in it. Coordinates are in pixels, with (0, 0) at the top left
+#[derive(Debug)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    kind: CanvasKind,
+    cells: Vec<u8>,
+    colors: Vec<Color>,
+}
+
+impl Canvas {
+    /// Create a new canvas covering width x height cells
+    pub fn new(width: usize, height: usize, kind: CanvasKind) -> Canvas {
+        Canvas{
+            width,
+            height,
+            kind,
+            cells: vec![0; width * height],
+            colors: vec![Color{r: 255, g: 255, b: 255}; width * height],
+        }
+    }
+    
+    /// Get the width of the canvas in cells
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    
+    /// Get the height of the canvas in cells
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    
+    /// Get the kind of canvas
+    pub fn kind(&self) -> CanvasKind {
+        self.kind
+    }
+    
+    /// Get the width of the canvas in pixels
+    pub fn pixel_width(&self) -> usize {
+        self.width * self.kind.cell_size().0
+    }
+    
+    /// Get the height of the canvas in pixels
+    pub fn pixel_height(&self) -> usize {
+        self.height * self.kind.cell_size().1
+    }
+    
+    /// Unset every pixel
+    pub fn clear(&mut self) {
+        for bits in self.cells.iter_mut() {
+            *bits = 0;
+        }
+    }
+    
+    /// Get the cell index and bit of a pixel, or `None` if out of bounds
+    fn locate(&self, point: Point) -> Option<(usize, u8)> {
+        let (x, y) = (point.x.floor(), point.y.floor());
+        if x < 0.0 || y < 0.0 || x >= self.pixel_width() as f64 || y >= self.pixel_height() as f64 {
+            return None;
+        }
+        
+        let (x, y) = (x as usize, y as usize);
+        let (cell_width, cell_height) = self.kind.cell_size();
+        let cell = (y / cell_height) * self.width + x / cell_width;
+        Some((cell, self.kind.bit(x % cell_width, y % cell_height)))
+    }
+    
+    /// Set a pixel, and the foreground color of its cell
+    pub fn set_pixel(&mut self, point: Point, color: Color) {
+        if let Some((cell, bit)) = self.locate(point) {
+            self.cells[cell] |= bit;
+            self.colors[cell] = color;
+        }
+    }
+    
+    /// Unset a pixel
+    pub fn unset_pixel(&mut self, point: Point) {
+        if let Some((cell, bit)) = self.locate(point) {
+            self.cells[cell] &= !bit;
+        }
+    }
+    
+    /// Check whether a pixel is set
+    pub fn get_pixel(&self, point: Point) -> bool {
+        self.locate(point).is_some_and(|(cell, bit)| self.cells[cell] & bit != 0)
+    }
+    
+    /// Draw a line with Bresenham's algorithm. The line is clipped to the
+    /// canvas first, so lines far outside of it don't take long to draw
+    pub fn line(&mut self, line: Line, color: Color) {
+        let ((mut x, mut y), (end_x, end_y)) = match self.clip(line) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        let (dx, dy) = ((end_x - x).abs(), -(end_y - y).abs());
+        let (step_x, step_y) = (if x < end_x { 1 } else { -1 }, if y < end_y { 1 } else { -1 });
+        let mut error = dx + dy;
+        
+        loop {
+            self.set_pixel(Point{x: x as f64, y: y as f64}, color);
+            if x == end_x && y == end_y {
+                break;
+            }
+            
+            let error2 = error * 2;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    
+    /// Clip a line to the canvas pixels with the Liang–Barsky algorithm,
+    /// returning its first and last pixel, or `None` if it misses the canvas
+    fn clip(&self, line: Line) -> Option<((i64, i64), (i64, i64))> {
+        let (x0, y0) = (line.start.x.floor(), line.start.y.floor());
+        let (x1, y1) = (line.end.x.floor(), line.end.y.floor());
+        if ![x0, y0, x1, y1].iter().all(|value| value.is_finite()) || self.cells.is_empty() {
+            return None;
+        }
+        
+        // Clip against the centers of the edge pixels
+        let (max_x, max_y) = ((self.pixel_width() - 1) as f64, (self.pixel_height() - 1) as f64);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        for &(p, q) in &[(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+            if p == 0.0 {
+                // Parallel to this edge, and either inside or outside of it
+                if q < 0.0 {
+                    return None;
+                }
+            }
+            else if p < 0.0 {
+                enter = enter.max(q / p);
+            }
+            else {
+                exit = exit.min(q / p);
+            }
+        }
+        if enter > exit {
+            return None;
+        }
+        
+        let point = |t: f64| ((x0 + dx * t).round() as i64, (y0 + dy * t).round() as i64);
+        Some((point(enter), point(exit)))
+    }
+    
+    /// Draw a circle outline with the midpoint circle algorithm. Circles
+    /// that miss the canvas are skipped, and ones much larger than it are
+    /// drawn row by row, so huge radii don't take long to draw
+    pub fn circle(&mut self, center: Point, radius: f64, color: Color) {
+        let (cx, cy) = (center.x.floor(), center.y.floor());
+        let radius = radius.round();
+        if !cx.is_finite() || !cy.is_finite() || !radius.is_finite() || radius < 0.0 {
+            return;
+        }
+        
+        let (width, height) = (self.pixel_width() as f64, self.pixel_height() as f64);
+        if cx + radius < 0.0 || cy + radius < 0.0 || cx - radius >= width || cy - radius >= height {
+            return;
+        }
+        // Nothing to draw if the whole canvas is inside the circle
+        let far_x = cx.abs().max((width - 1.0 - cx).abs());
+        let far_y = cy.abs().max((height - 1.0 - cy).abs());
+        if far_x.hypot(far_y) < radius - 1.0 {
+            return;
+        }
+        if radius > width + height {
+            self.large_circle(cx, cy, radius, color);
+            return;
+        }
+        
+        let radius = radius as i64;
+        let (mut x, mut y) = (radius, 0i64);
+        let mut error = 1 - radius;
+        while x >= y {
+            // Plot all 8 octants
+            for &(px, py) in &[(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                self.set_pixel(Point{x: cx + px as f64, y: cy + py as f64}, color);
+            }
+            
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            }
+            else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+    
+    /// Draw a circle by finding where it crosses each pixel row and column
+    /// of the canvas, for circles too large to walk around
+    fn large_circle(&mut self, cx: f64, cy: f64, radius: f64, color: Color) {
+        // Distance from the center along one axis, for an offset along the
+        // other, factored so it stays precise close to the edge
+        let reach = |offset: f64| ((radius - offset) * (radius + offset)).sqrt().round();
+        for y in 0..self.pixel_height() {
+            let offset = (y as f64 - cy).abs();
+            if offset <= radius {
+                let dx = reach(offset);
+                self.set_pixel(Point{x: cx - dx, y: y as f64}, color);
+                self.set_pixel(Point{x: cx + dx, y: y as f64}, color);
+            }
+        }
+        for x in 0..self.pixel_width() {
+            let offset = (x as f64 - cx).abs();
+            if offset <= radius {
+                let dy = reach(offset);
+                self.set_pixel(Point{x: x as f64, y: cy - dy}, color);
+                self.set_pixel(Point{x: x as f64, y: cy + dy}, color);
+            }
+        }
+    }
+    
+    /// Draw the canvas onto a framebuffer, with its top left cell at (x, y).
+    /// Only cells with pixels set are touched, and only their character and
+    /// foreground color, so the canvas overlays whatever is underneath
+    pub fn draw(&self, framebuffer: &mut Framebuffer, x: usize, y: usize) {
+        for cell_y in 0..self.height {
+            for cell_x in 0..self.width {
+                let cell = cell_y * self.width + cell_x;
+                let bits = self.cells[cell];
+                if bits != 0 {
+                    framebuffer.set_cell(x + cell_x, y + cell_y, Some(self.kind.glyph(bits)), Some(self.colors[cell]), None);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const WHITE: Color = Color{r: 255, g: 255, b: 255};
+    
+    fn line(x0: f64, y0: f64, x1: f64, y1: f64) -> Line {
+        Line{start: Point{x: x0, y: y0}, end: Point{x: x1, y: y1}}
+    }
+    
+    /// Get the set pixels of a canvas, row by row
+    fn pixels(canvas: &Canvas) -> Vec<String> {
+        (0..canvas.pixel_height()).map(|y| {
+            (0..canvas.pixel_width()).map(|x| if canvas.get_pixel(Point{x: x as f64, y: y as f64}) { '#' } else { '.' }).collect()
+        }).collect()
+    }
+    
+    #[test]
+    fn lines_inside_are_unchanged() {
+        let mut canvas = Canvas::new(2, 2, CanvasKind::Quadrant);
+        canvas.line(line(0.0, 0.0, 3.0, 3.0), WHITE);
+        canvas.line(line(3.5, 0.2, 0.0, 0.9), WHITE);
+        assert_eq!(pixels(&canvas), ["####", ".#..", "..#.", "...#"]);
+    }
+    
+    #[test]
+    fn lines_are_clipped() {
+        let mut canvas = Canvas::new(2, 2, CanvasKind::Quadrant);
+        // Would take ages to walk without clipping
+        canvas.line(line(-1e15, 1.0, 1e15, 1.0), WHITE);
+        canvas.line(line(2.0, -1e12, 2.0, 1e12), WHITE);
+        canvas.line(line(-10.0, 13.0, 10.0, -7.0), WHITE);
+        assert_eq!(pixels(&canvas), ["..##", "####", ".##.", "#.#."]);
+    }
+    
+    #[test]
+    fn lines_outside_are_skipped() {
+        let mut canvas = Canvas::new(2, 2, CanvasKind::Quadrant);
+        canvas.line(line(-5.0, -1.0, 10.0, -1.0), WHITE);
+        canvas.line(line(4.0, 0.0, 4.0, 3.0), WHITE);
+        canvas.line(line(-3.0, 0.0, 0.0, -3.0), WHITE);
+        canvas.line(line(f64::NAN, 0.0, 1.0, 1.0), WHITE);
+        assert_eq!(pixels(&canvas), ["....", "....", "....", "...."]);
+        
+        let mut empty = Canvas::new(0, 0, CanvasKind::Braille);
+        empty.line(line(0.0, 0.0, 1.0, 1.0), WHITE);
+    }
+    
+    #[test]
+    fn braille_pixels() {
+        let mut canvas = Canvas::new(2, 1, CanvasKind::Braille);
+        assert_eq!((canvas.pixel_width(), canvas.pixel_height()), (4, 4));
+        canvas.line(line(0.0, 0.0, 0.0, 3.0), WHITE);
+        canvas.set_pixel(Point{x: 3.0, y: 3.0}, WHITE);
+        canvas.set_pixel(Point{x: 2.5, y: 1.9}, WHITE);
+        assert_eq!(pixels(&canvas), ["#...", "#.#.", "#...", "#..#"]);
+        
+        canvas.unset_pixel(Point{x: 0.0, y: 3.0});
+        assert_eq!(pixels(&canvas), ["#...", "#.#.", "#...", "...#"]);
+    }
+    
+    #[test]
+    fn circles() {
+        let mut canvas = Canvas::new(4, 4, CanvasKind::Quadrant);
+        canvas.circle(Point{x: 3.5, y: 3.5}, 2.0, WHITE);
+        assert_eq!(pixels(&canvas), [
+            "........",
+            "..###...",
+            ".#...#..",
+            ".#...#..",
+            ".#...#..",
+            "..###...",
+            "........",
+            "........",
+        ]);
+        
+        canvas.clear();
+        canvas.circle(Point{x: 1.0, y: 1.0}, 0.0, WHITE);
+        assert_eq!(pixels(&canvas)[1], ".#......");
+    }
+    
+    #[test]
+    fn circles_are_clipped() {
+        let mut canvas = Canvas::new(2, 2, CanvasKind::Quadrant);
+        canvas.circle(Point{x: 0.0, y: 0.0}, 3.0, WHITE);
+        assert_eq!(pixels(&canvas), ["...#", "...#", "..#.", "##.."]);
+        
+        // Would take ages to walk around
+        canvas.clear();
+        canvas.circle(Point{x: -1e12 + 2.0, y: 1.0}, 1e12, WHITE);
+        canvas.circle(Point{x: 1.0, y: 1e12 + 2.0}, 1e12, WHITE);
+        assert_eq!(pixels(&canvas), ["..#.", "..#.", "####", "..#."]);
+    }
+    
+    #[test]
+    fn circles_outside_are_skipped() {
+        let mut canvas = Canvas::new(2, 2, CanvasKind::Quadrant);
+        canvas.circle(Point{x: -5.0, y: 1.0}, 3.0, WHITE);
+        canvas.circle(Point{x: 1.0, y: 1.0}, 1e15, WHITE);
+        canvas.circle(Point{x: 1.0, y: 1.0}, f64::INFINITY, WHITE);
+        canvas.circle(Point{x: f64::NAN, y: 1.0}, 1.0, WHITE);
+        canvas.circle(Point{x: 1.0, y: 1.0}, f64::NAN, WHITE);
+        canvas.circle(Point{x: 1.0, y: 1.0}, -2.0, WHITE);
+        assert_eq!(pixels(&canvas), ["....", "....", "....", "...."]);
+    }
+    
+    #[test]
+    fn draw_onto_framebuffer() {
+        let mut canvas = Canvas::new(2, 1, CanvasKind::Quadrant);
+        canvas.set_pixel(Point{x: 0.0, y: 0.0}, WHITE);
+        canvas.set_pixel(Point{x: 3.0, y: 0.0}, WHITE);
+        canvas.set_pixel(Point{x: 3.0, y: 1.0}, WHITE);
+        
+        let black = Color{r: 0, g: 0, b: 0};
+        let red = Color{r: 255, g: 0, b: 0};
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.clear('x', black, red);
+        canvas.draw(&mut framebuffer, 1, 1);
+        canvas.draw(&mut framebuffer, 3, 0);
+        let cell = |x, y| {
+            let cell = framebuffer.get_cell(x, y).unwrap();
+            (cell.grapheme.to_string(), cell.fg, cell.bg)
+        };
+        assert_eq!(cell(0, 1), ("x".to_string(), black, red));
+        assert_eq!(cell(1, 1), ("\u{2598}".to_string(), WHITE, red));
+        assert_eq!(cell(2, 1), ("\u{2590}".to_string(), WHITE, red));
+        assert_eq!(cell(3, 0), ("\u{2598}".to_string(), WHITE, red));
+        assert_eq!(cell(3, 1), ("x".to_string(), black, red));
+    }
+}
@@ -0,0 +1,418 @@
+/// A color struct
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// A color with an alpha channel. An alpha of 0 is fully transparent and 255
+/// is fully opaque
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    /// Create a color from an opaque color and an alpha
+    pub fn new(color: Color, a: u8) -> Rgba {
+        Rgba{r: color.r, g: color.g, b: color.b, a}
+    }
+    
+    /// Get the color without its alpha channel
+    pub fn color(&self) -> Color {
+        Color{r: self.r, g: self.g, b: self.b}
+    }
+}
+
+impl From<Color> for Rgba {
+    fn from(color: Color) -> Rgba {
+        Rgba::new(color, 255)
+    }
+}
+
+/// How a translucent color is combined with the color below it
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The top color is drawn over the bottom color
+    #[default]
+    Normal,
+    /// Channels are added, brightening the bottom color
+    Add,
+    /// Channels are multiplied, darkening the bottom color
+    Multiply,
+    /// Inverted channels are multiplied, brightening the bottom color
+    Screen,
+}
+
+impl BlendMode {
+    /// Blend `top` over `bottom`. The blended color is mixed with `bottom`
+    /// by the top color's alpha, scaled by `opacity` (0 to 1)
+    pub fn blend(&self, bottom: Color, top: Rgba, opacity: f64) -> Color {
+        let alpha = top.a as f64 / 255.0 * opacity.clamp(0.0, 1.0);
+        let blend_channel = |b: u8, t: u8| -> u8 {
+            let (bf, tf) = (b as f64, t as f64);
+            let blended = match self {
+                BlendMode::Normal => tf,
+                BlendMode::Add => (bf + tf).min(255.0),
+                BlendMode::Multiply => bf * tf / 255.0,
+                BlendMode::Screen => 255.0 - (255.0 - bf) * (255.0 - tf) / 255.0,
+            };
+            (bf + (blended - bf) * alpha).round() as u8
+        };
+        
+        Color{
+            r: blend_channel(bottom.r, top.r),
+            g: blend_channel(bottom.g, top.g),
+            b: blend_channel(bottom.b, top.b),
+        }
+    }
+}
+
+/// A color in a perceptual L*a*b* space (CIELAB or OKLab, depending on where
+/// it came from)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+/// Convert an sRGB channel to linear light in the range 0 to 1
+fn srgb_to_linear(channel: u8) -> f64 {
+    let c = channel as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    }
+    else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear light channel in the range 0 to 1 to sRGB
+fn linear_to_srgb(channel: f64) -> u8 {
+    let c = if channel <= 0.0031308 {
+        channel * 12.92
+    }
+    else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+impl Color {
+    /// Get the manhattan distance between two colors
+    pub fn dist(&self, color: &Color) -> u16 {
+        ((color.r as i16 - self.r as i16).abs() + (color.g as i16 - self.g as i16).abs() + (color.b as i16 - self.b as i16).abs()) as u16
+    }
+    
+    /// Get the "redmean" weighted euclidean distance between two colors. A
+    /// cheap approximation of perceptual distance that works on raw sRGB
+    pub fn dist_redmean(&self, color: &Color) -> f64 {
+        let r_mean = (self.r as f64 + color.r as f64) * 0.5;
+        let dr = self.r as f64 - color.r as f64;
+        let dg = self.g as f64 - color.g as f64;
+        let db = self.b as f64 - color.b as f64;
+        ((2.0 + r_mean / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - r_mean) / 256.0) * db * db).sqrt()
+    }
+    
+    /// Get the linear light channels of this color, in the range 0 to 1
+    pub fn to_linear(&self) -> [f64; 3] {
+        [srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b)]
+    }
+    
+    /// Create a color from linear light channels in the range 0 to 1
+    pub fn from_linear(channels: [f64; 3]) -> Color {
+        Color{
+            r: linear_to_srgb(channels[0]),
+            g: linear_to_srgb(channels[1]),
+            b: linear_to_srgb(channels[2]),
+        }
+    }
+    
+    /// Mix two colors in linear light, like the eye does with fine patterns.
+    /// `amount` is how much of `other` to mix in, from 0 to 1
+    pub fn mix(&self, other: &Color, amount: f64) -> Color {
+        let (a, b) = (self.to_linear(), other.to_linear());
+        Color::from_linear([
+            a[0] + (b[0] - a[0]) * amount,
+            a[1] + (b[1] - a[1]) * amount,
+            a[2] + (b[2] - a[2]) * amount,
+        ])
+    }
+    
+    /// Convert to CIELAB (D65 white point). L* is in the range 0 to 100
+    pub fn to_lab(&self) -> Lab {
+        let [r, g, b] = self.to_linear();
+        
+        // Linear sRGB to CIE XYZ, normalized to the D65 white point
+        let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+        let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+        let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+        
+        fn f(t: f64) -> f64 {
+            const DELTA: f64 = 6.0 / 29.0;
+            if t > DELTA * DELTA * DELTA {
+                t.cbrt()
+            }
+            else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        }
+        
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+        Lab{
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+    
+    /// Convert to OKLab. L is in the range 0 to 1
+    pub fn to_oklab(&self) -> Lab {
+        let [r, g, b] = self.to_linear();
+        
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+        
+        Lab{
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+        }
+    }
+}
+
+impl Lab {
+    /// Get the euclidean distance between two colors. For CIELAB colors this
+    /// is CIE76 delta E
+    pub fn delta_e76(&self, other: &Lab) -> f64 {
+        let (dl, da, db) = (self.l - other.l, self.a - other.a, self.b - other.b);
+        (dl * dl + da * da + db * db).sqrt()
+    }
+    
+    /// Get the CIEDE2000 delta E between two CIELAB colors
+    pub fn delta_e2000(&self, other: &Lab) -> f64 {
+        const POW25_7: f64 = 6103515625.0; // 25^7
+        
+        let c1 = self.a.hypot(self.b);
+        let c2 = other.a.hypot(other.b);
+        let c_mean_7 = ((c1 + c2) * 0.5).powi(7);
+        let g = 0.5 * (1.0 - (c_mean_7 / (c_mean_7 + POW25_7)).sqrt());
+        
+        // Adjusted a*, chroma and hue (in degrees)
+        let a1 = (1.0 + g) * self.a;
+        let a2 = (1.0 + g) * other.a;
+        let c1 = a1.hypot(self.b);
+        let c2 = a2.hypot(other.b);
+        let hue = |b: f64, a: f64| if b == 0.0 && a == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+        let h1 = hue(self.b, a1);
+        let h2 = hue(other.b, a2);
+        
+        // Differences
+        let dl = other.l - self.l;
+        let dc = c2 - c1;
+        let chroma_product = c1 * c2;
+        let mut dh = h2 - h1;
+        if chroma_product == 0.0 {
+            dh = 0.0;
+        }
+        else if dh > 180.0 {
+            dh -= 360.0;
+        }
+        else if dh < -180.0 {
+            dh += 360.0;
+        }
+        let dh = 2.0 * chroma_product.sqrt() * (dh * 0.5).to_radians().sin();
+        
+        // Means
+        let l_mean = (self.l + other.l) * 0.5;
+        let c_mean = (c1 + c2) * 0.5;
+        let h_mean = if chroma_product == 0.0 {
+            h1 + h2
+        }
+        else if (h1 - h2).abs() <= 180.0 {
+            (h1 + h2) * 0.5
+        }
+        else if h1 + h2 < 360.0 {
+            (h1 + h2 + 360.0) * 0.5
+        }
+        else {
+            (h1 + h2 - 360.0) * 0.5
+        };
+        
+        // Weighting functions
+        let t = 1.0
+            - 0.17 * (h_mean - 30.0).to_radians().cos()
+            + 0.24 * (2.0 * h_mean).to_radians().cos()
+            + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+            - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+        let d_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+        let c_mean_7 = c_mean.powi(7);
+        let r_c = 2.0 * (c_mean_7 / (c_mean_7 + POW25_7)).sqrt();
+        let l_50 = (l_mean - 50.0) * (l_mean - 50.0);
+        let s_l = 1.0 + 0.015 * l_50 / (20.0 + l_50).sqrt();
+        let s_c = 1.0 + 0.045 * c_mean;
+        let s_h = 1.0 + 0.015 * c_mean * t;
+        let r_t = -(2.0 * d_theta).to_radians().sin() * r_c;
+        
+        let (l_term, c_term, h_term) = (dl / s_l, dc / s_c, dh / s_h);
+        (l_term * l_term + c_term * c_term + h_term * h_term + r_t * c_term * h_term).sqrt()
+    }
+}
+
+/// How the distance between two colors is measured when picking the nearest
+/// console color
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorMetric {
+    /// Manhattan distance in sRGB. Fast, but not perceptual
+    #[default]
+    Manhattan,
+    /// Weighted euclidean distance in sRGB
+    Redmean,
+    /// Euclidean distance in CIELAB
+    Cie76,
+    /// CIEDE2000 delta E in CIELAB. The most accurate, and the slowest
+    Ciede2000,
+    /// Euclidean distance in OKLab
+    Oklab,
+}
+
+impl ColorMetric {
+    /// Get the distance between two colors. Only comparable with other
+    /// distances from the same metric
+    pub fn distance(&self, a: &Color, b: &Color) -> f64 {
+        match self {
+            ColorMetric::Manhattan => a.dist(b) as f64,
+            ColorMetric::Redmean => a.dist_redmean(b),
+            ColorMetric::Cie76 => a.to_lab().delta_e76(&b.to_lab()),
+            ColorMetric::Ciede2000 => a.to_lab().delta_e2000(&b.to_lab()),
+            ColorMetric::Oklab => a.to_oklab().delta_e76(&b.to_oklab()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn assert_lab(actual: Lab, expected: [f64; 3], tolerance: f64) {
+        let actual_values = [actual.l, actual.a, actual.b];
+        for (actual_value, expected_value) in actual_values.iter().zip(expected.iter()) {
+            assert!((actual_value - expected_value).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+    
+    #[test]
+    fn ciede2000_reference_pairs() {
+        // Test data from Sharma, Wu and Dalal, "The CIEDE2000 color-difference
+        // formula: implementation notes, supplementary test data, and
+        // mathematical observations" (2005)
+        const PAIRS: [([f64; 3], [f64; 3], f64); 34] = [
+            ([50.0000, 2.6772, -79.7751], [50.0000, 0.0000, -82.7485], 2.0425),
+            ([50.0000, 3.1571, -77.2803], [50.0000, 0.0000, -82.7485], 2.8615),
+            ([50.0000, 2.8361, -74.0200], [50.0000, 0.0000, -82.7485], 3.4412),
+            ([50.0000, -1.3802, -84.2814], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, -1.1848, -84.8006], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, -0.9009, -85.5211], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, 0.0000, 0.0000], [50.0000, -1.0000, 2.0000], 2.3669),
+            ([50.0000, -1.0000, 2.0000], [50.0000, 0.0000, 0.0000], 2.3669),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0009], 7.1792),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0010], 7.1792),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0011], 7.2195),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0012], 7.2195),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0009, -2.4900], 4.8045),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0010, -2.4900], 4.8045),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0011, -2.4900], 4.7461),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 0.0000, -2.5000], 4.3065),
+            ([50.0000, 2.5000, 0.0000], [73.0000, 25.0000, -18.0000], 27.1492),
+            ([50.0000, 2.5000, 0.0000], [61.0000, -5.0000, 29.0000], 22.8977),
+            ([50.0000, 2.5000, 0.0000], [56.0000, -27.0000, -3.0000], 31.9030),
+            ([50.0000, 2.5000, 0.0000], [58.0000, 24.0000, 15.0000], 19.4535),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.1736, 0.5854], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.2972, 0.0000], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 1.8634, 0.5757], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.2592, 0.3350], 1.0000),
+            ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+            ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630),
+            ([61.2901, 3.7196, -5.3901], [61.4292, 2.2480, -4.9620], 1.8731),
+            ([35.0831, -44.1164, 3.7933], [35.0232, -40.0716, 1.5901], 1.8645),
+            ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+            ([36.4612, 47.8580, 18.3852], [36.2715, 50.5065, 21.2231], 1.4146),
+            ([90.8027, -2.0831, 1.4410], [91.1528, -1.6435, 0.0447], 1.4441),
+            ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381),
+            ([6.7747, -0.2908, -2.4247], [5.8714, -0.0985, -2.2286], 0.6377),
+            ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+        ];
+        
+        let lab = |[l, a, b]: [f64; 3]| Lab{l, a, b};
+        for (i, &(first, second, expected)) in PAIRS.iter().enumerate() {
+            let (first, second) = (lab(first), lab(second));
+            for delta in [first.delta_e2000(&second), second.delta_e2000(&first)] {
+                assert!((delta - expected).abs() < 0.00005, "pair {}: {} != {}", i + 1, delta, expected);
+            }
+        }
+    }
+    
+    #[test]
+    fn srgb_to_lab() {
+        // D65 values, as given by Bruce Lindbloom's color calculator
+        assert_lab(Color{r: 255, g: 255, b: 255}.to_lab(), [100.0, 0.0, 0.0], 0.001);
+        assert_lab(Color{r: 0, g: 0, b: 0}.to_lab(), [0.0, 0.0, 0.0], 0.001);
+        assert_lab(Color{r: 255, g: 0, b: 0}.to_lab(), [53.2408, 80.0925, 67.2032], 0.001);
+        assert_lab(Color{r: 0, g: 255, b: 0}.to_lab(), [87.7347, -86.1827, 83.1793], 0.001);
+        assert_lab(Color{r: 0, g: 0, b: 255}.to_lab(), [32.2970, 79.1875, -107.8602], 0.001);
+        assert_lab(Color{r: 119, g: 119, b: 119}.to_lab(), [50.0, 0.0, 0.0], 0.05);
+    }
+    
+    #[test]
+    fn srgb_to_oklab() {
+        // Values from Björn Ottosson's OKLab reference implementation
+        assert_lab(Color{r: 255, g: 255, b: 255}.to_oklab(), [1.0, 0.0, 0.0], 0.0001);
+        assert_lab(Color{r: 0, g: 0, b: 0}.to_oklab(), [0.0, 0.0, 0.0], 0.0001);
+        assert_lab(Color{r: 255, g: 0, b: 0}.to_oklab(), [0.627955, 0.224863, 0.125846], 0.0001);
+        assert_lab(Color{r: 0, g: 255, b: 0}.to_oklab(), [0.866440, -0.233888, 0.179498], 0.0001);
+        assert_lab(Color{r: 0, g: 0, b: 255}.to_oklab(), [0.452014, -0.032457, -0.311528], 0.0001);
+    }
+    
+    #[test]
+    fn blend_modes() {
+        let bottom = Color{r: 100, g: 150, b: 200};
+        let top = Rgba{r: 200, g: 100, b: 50, a: 255};
+        let blend = |mode: BlendMode, top: Rgba, opacity: f64| {
+            let Color{r, g, b} = mode.blend(bottom, top, opacity);
+            [r, g, b]
+        };
+        
+        assert_eq!(blend(BlendMode::Normal, top, 1.0), [200, 100, 50]);
+        assert_eq!(blend(BlendMode::Add, top, 1.0), [255, 250, 250]);
+        assert_eq!(blend(BlendMode::Multiply, top, 1.0), [78, 59, 39]);
+        assert_eq!(blend(BlendMode::Screen, top, 1.0), [222, 191, 211]);
+    }
+    
+    #[test]
+    fn blend_alpha_and_opacity() {
+        let bottom = Color{r: 100, g: 150, b: 200};
+        let top = Rgba{r: 200, g: 100, b: 50, a: 255};
+        let half = Rgba{a: 128, ..top};
+        let blend = |mode: BlendMode, top: Rgba, opacity: f64| {
+            let Color{r, g, b} = mode.blend(bottom, top, opacity);
+            [r, g, b]
+        };
+        
+        // Alpha and opacity both scale how much of the blend is mixed in
+        assert_eq!(blend(BlendMode::Normal, half, 1.0), [150, 125, 125]);
+        assert_eq!(blend(BlendMode::Normal, top, 0.5), [150, 125, 125]);
+        assert_eq!(blend(BlendMode::Add, top, 0.5), [178, 200, 225]);
+        assert_eq!(blend(BlendMode::Normal, half, 0.5), [125, 137, 162]);
+        // Fully transparent, and opacity out of range
+        assert_eq!(blend(BlendMode::Screen, Rgba{a: 0, ..top}, 1.0), [100, 150, 200]);
+        assert_eq!(blend(BlendMode::Normal, top, 0.0), [100, 150, 200]);
+        assert_eq!(blend(BlendMode::Normal, top, -1.0), [100, 150, 200]);
+        assert_eq!(blend(BlendMode::Normal, top, 2.0), [200, 100, 50]);
+    }
+}
@@ -1,5 +0,0 @@
-pub mod color;
-pub mod framebuffer;
-pub mod geometry;
-pub mod world;
-mod csi_color;
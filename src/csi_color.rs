@@ -0,0 +1,151 @@
+use crate::color::{Color, ColorMetric};
+use crate::palette::Palette;
+
+/// Color depth used when sending colors to the console
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 16 colors. Each color is snapped to the nearest entry of the
+    /// framebuffer's palette
+    #[default]
+    Ansi16,
+    /// xterm 256 color palette (`38;5;n`). Uses the 6x6x6 color cube and the
+    /// grayscale ramp, not the first 16 (terminal dependent) entries
+    Ansi256,
+    /// 24-bit color (`38;2;r;g;b`)
+    TrueColor,
+    /// No colors are sent, the console's default colors are used
+    Monochrome,
+}
+
+/// A color as it is sent to the console
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleColor {
+    /// Index into the 16 color palette
+    Csi(usize),
+    /// xterm 256 color palette index
+    Indexed(u8),
+    /// 24-bit color
+    Rgb(Color),
+    /// The console's default color
+    Default,
+}
+
+impl ConsoleColor {
+    /// Get the color the console shows for this color, given the console's
+    /// 16 color palette. Returns `None` for the console's default color
+    pub fn to_color(&self, palette: &Palette) -> Option<Color> {
+        match *self {
+            ConsoleColor::Csi(index) => Some(palette.colors[index]),
+            ConsoleColor::Indexed(index) => Some(xterm256_color(index)),
+            ConsoleColor::Rgb(color) => Some(color),
+            ConsoleColor::Default => None,
+        }
+    }
+}
+
+/// Channel levels of the xterm 256 color cube
+const XTERM_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Get the index of the nearest xterm color cube level to a channel value
+fn xterm_cube_level(value: u8) -> usize {
+    // Levels are 40 apart except for the first step, so the midpoints
+    // between levels are 48, 115, 155, 195 and 235
+    if value < 48 {
+        0
+    }
+    else if value < 115 {
+        1
+    }
+    else {
+        ((value as usize - 35) / 40).min(5)
+    }
+}
+
+/// Get the color of an xterm 256 color palette index. Only valid for the
+/// color cube and grayscale ramp (16 to 255)
+pub fn xterm256_color(index: u8) -> Color {
+    if index >= 232 {
+        let level = 8 + (index - 232) * 10;
+        Color{r: level, g: level, b: level}
+    }
+    else {
+        let cube = index.saturating_sub(16) as usize;
+        Color{
+            r: XTERM_CUBE_LEVELS[cube / 36],
+            g: XTERM_CUBE_LEVELS[(cube / 6) % 6],
+            b: XTERM_CUBE_LEVELS[cube % 6],
+        }
+    }
+}
+
+/// Get the nearest xterm 256 color palette index to a color. With the
+/// manhattan metric only the nearest color cube entry and the nearest
+/// grayscale ramp entry are considered, other metrics search every entry
+pub fn xterm256_index(color: &Color, metric: ColorMetric) -> u8 {
+    if metric != ColorMetric::Manhattan {
+        return (16..=255u8)
+            .min_by(|&a, &b| metric.distance(&xterm256_color(a), color).total_cmp(&metric.distance(&xterm256_color(b), color)))
+            .unwrap();
+    }
+    
+    let (r, g, b) = (xterm_cube_level(color.r), xterm_cube_level(color.g), xterm_cube_level(color.b));
+    let cube_index = (16 + r * 36 + g * 6 + b) as u8;
+    
+    // Grayscale ramp goes from 8 to 238 in steps of 10
+    let average = (color.r as usize + color.g as usize + color.b as usize) / 3;
+    let gray_index = (232 + (average.saturating_sub(3) / 10).min(23)) as u8;
+    
+    if xterm256_color(gray_index).dist(color) < xterm256_color(cube_index).dist(color) {
+        gray_index
+    }
+    else {
+        cube_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const METRICS: [ColorMetric; 5] = [ColorMetric::Manhattan, ColorMetric::Redmean, ColorMetric::Cie76, ColorMetric::Ciede2000, ColorMetric::Oklab];
+    
+    #[test]
+    fn xterm256_colors() {
+        assert_eq!(xterm256_color(16), Color{r: 0, g: 0, b: 0});
+        assert_eq!(xterm256_color(67), Color{r: 95, g: 135, b: 175});
+        assert_eq!(xterm256_color(231), Color{r: 255, g: 255, b: 255});
+        assert_eq!(xterm256_color(232), Color{r: 8, g: 8, b: 8});
+        assert_eq!(xterm256_color(255), Color{r: 238, g: 238, b: 238});
+    }
+    
+    #[test]
+    fn palette_colors_map_to_themselves() {
+        for index in 16..=255 {
+            let color = xterm256_color(index);
+            for metric in METRICS {
+                assert_eq!(xterm256_color(xterm256_index(&color, metric)), color, "{:?} with {:?}", color, metric);
+            }
+        }
+    }
+    
+    #[test]
+    fn metrics_change_the_nearest_index() {
+        // A dark green between the color cube and the grayscale ramp, where
+        // every metric disagrees
+        let color = Color{r: 0, g: 60, b: 30};
+        let indices: Vec<u8> = METRICS.iter().map(|&metric| xterm256_index(&color, metric)).collect();
+        assert_eq!(indices, [234, 235, 23, 22, 236]);
+        
+        // Except for the manhattan shortcut, the nearest entry is searched for
+        for &metric in &METRICS[1..] {
+            let index = xterm256_index(&color, metric);
+            let distance = metric.distance(&xterm256_color(index), &color);
+            assert!((16..=255).all(|other| metric.distance(&xterm256_color(other), &color) >= distance), "{:?}", metric);
+        }
+        
+        // CIE76 picks a darker red than manhattan distance does
+        let red = Color{r: 200, g: 30, b: 30};
+        assert_eq!(xterm256_index(&red, ColorMetric::Manhattan), 160);
+        assert_eq!(xterm256_index(&red, ColorMetric::Cie76), 124);
+    }
+}
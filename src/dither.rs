@@ -0,0 +1,180 @@
+use crate::color::Color;
+
+/// Dithering applied when a frame is quantized to a console palette
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Every cell is snapped to its nearest palette color
+    #[default]
+    None,
+    /// 4x4 Bayer ordered dithering
+    Bayer,
+    /// Floyd-Steinberg error diffusion
+    FloydSteinberg,
+    /// Atkinson error diffusion. Only diffuses 3/4 of the error, which keeps
+    /// more contrast than Floyd-Steinberg
+    Atkinson,
+}
+
+/// 4x4 Bayer threshold matrix
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5],
+];
+
+/// Floyd-Steinberg kernel, as (x offset, y offset, weight)
+const FLOYD_STEINBERG: [(isize, usize, f64); 4] = [
+    ( 1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    ( 0, 1, 5.0 / 16.0),
+    ( 1, 1, 1.0 / 16.0),
+];
+
+/// Atkinson kernel, as (x offset, y offset, weight)
+const ATKINSON: [(isize, usize, f64); 6] = [
+    ( 1, 0, 1.0 / 8.0),
+    ( 2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    ( 0, 1, 1.0 / 8.0),
+    ( 1, 1, 1.0 / 8.0),
+    ( 0, 2, 1.0 / 8.0),
+];
+
+fn clamp_channel(value: f64) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Quantize a width x height plane of colors, in row order. `quantize` is
+/// called once per cell with the cell index and the dithered target color,
+/// and returns the color that will actually be shown. `spread` is how far
+/// ordered dithering may push a channel, and should be about the distance
+/// between palette colors
+pub(crate) fn dither_plane<F: FnMut(usize, Color) -> Color>(dither: Dither, width: usize, height: usize, colors: &[Color], spread: f64, mut quantize: F) {
+    let kernel: &[(isize, usize, f64)] = match dither {
+        Dither::None => {
+            for (i, color) in colors.iter().enumerate() {
+                quantize(i, *color);
+            }
+            return;
+        },
+        Dither::Bayer => {
+            let mut i = 0;
+            for y in 0..height {
+                for x in 0..width {
+                    let offset = ((BAYER_4X4[y % 4][x % 4] as f64 + 0.5) / 16.0 - 0.5) * spread;
+                    let color = colors[i];
+                    quantize(i, Color{
+                        r: clamp_channel(color.r as f64 + offset),
+                        g: clamp_channel(color.g as f64 + offset),
+                        b: clamp_channel(color.b as f64 + offset),
+                    });
+                    i += 1;
+                }
+            }
+            return;
+        },
+        Dither::FloydSteinberg => &FLOYD_STEINBERG,
+        Dither::Atkinson => &ATKINSON,
+    };
+    
+    // Accumulated error per cell and channel
+    let mut error = vec![[0.0f64; 3]; width * height];
+    let mut i = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let color = colors[i];
+            let target = [
+                color.r as f64 + error[i][0],
+                color.g as f64 + error[i][1],
+                color.b as f64 + error[i][2],
+            ];
+            let shown = quantize(i, Color{
+                r: clamp_channel(target[0]),
+                g: clamp_channel(target[1]),
+                b: clamp_channel(target[2]),
+            });
+            let cell_error = [
+                target[0] - shown.r as f64,
+                target[1] - shown.g as f64,
+                target[2] - shown.b as f64,
+            ];
+            
+            for &(dx, dy, weight) in kernel {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    continue;
+                }
+                
+                let neighbour = &mut error[ny * width + nx as usize];
+                for channel in 0..3 {
+                    neighbour[channel] += cell_error[channel] * weight;
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Dither a plane of one gray level to black and white, returning a
+    /// string per row with `#` for white
+    fn black_and_white(dither: Dither, width: usize, height: usize, level: u8, spread: f64) -> Vec<String> {
+        let colors = vec![Color{r: level, g: level, b: level}; width * height];
+        let mut white = vec![false; width * height];
+        dither_plane(dither, width, height, &colors, spread, |i, target| {
+            white[i] = target.r >= 128;
+            let level = if white[i] { 255 } else { 0 };
+            Color{r: level, g: level, b: level}
+        });
+        white.chunks(width).map(|row| row.iter().map(|&white| if white { '#' } else { '.' }).collect()).collect()
+    }
+    
+    fn white_cells(rows: &[String]) -> usize {
+        rows.iter().map(|row| row.matches('#').count()).sum()
+    }
+    
+    #[test]
+    fn no_dither() {
+        assert_eq!(black_and_white(Dither::None, 4, 2, 127, 255.0), ["....", "...."]);
+        assert_eq!(black_and_white(Dither::None, 4, 2, 128, 255.0), ["####", "####"]);
+    }
+    
+    #[test]
+    fn bayer() {
+        // Cells whose threshold is in the top half of the matrix turn white
+        assert_eq!(black_and_white(Dither::Bayer, 4, 4, 128, 255.0), [".#.#", "#.#.", ".#.#", "#.#."]);
+        assert_eq!(black_and_white(Dither::Bayer, 6, 4, 64, 255.0), [
+            "......",
+            "#.#.#.",
+            "......",
+            "#.#.#.",
+        ]);
+        // No spread, no dithering
+        assert_eq!(black_and_white(Dither::Bayer, 4, 1, 100, 0.0), ["...."]);
+    }
+    
+    #[test]
+    fn floyd_steinberg() {
+        // 100 is shown as black, and 7/16 of the error pushes the next cell
+        // over the threshold
+        assert_eq!(black_and_white(Dither::FloydSteinberg, 4, 1, 100, 0.0), [".#.."]);
+        // The average level is kept
+        let rows = black_and_white(Dither::FloydSteinberg, 16, 16, 64, 0.0);
+        assert!((60..=68).contains(&white_cells(&rows)), "{:?}", rows);
+    }
+    
+    #[test]
+    fn atkinson() {
+        // Only 1/8 of the error goes to each of the next two cells, so the
+        // first white cell comes later than with Floyd-Steinberg
+        assert_eq!(black_and_white(Dither::Atkinson, 4, 1, 100, 0.0), ["...#"]);
+        // A quarter of the error is dropped, so a dark gray that
+        // Floyd-Steinberg still dithers becomes black
+        assert_eq!(white_cells(&black_and_white(Dither::Atkinson, 16, 16, 32, 0.0)), 0);
+        assert!(white_cells(&black_and_white(Dither::FloydSteinberg, 16, 16, 32, 0.0)) > 24);
+    }
+}
@@ -0,0 +1,748 @@
+//! 2D drawing primitives for `Framebuffer`: rectangles, lines, box-drawing
+//! frames and text
+
+use crate::attributes::Attributes;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::unicode::{Grapheme, char_width, graphemes, is_control, str_width};
+
+/// Line weights of box-drawing arms. Each arm takes 2 bits of an arms mask,
+/// in the order up, right, down, left
+const NONE: u8 = 0;
+const LIGHT: u8 = 1;
+const HEAVY: u8 = 2;
+const DOUBLE: u8 = 3;
+
+const UP: u32 = 0;
+const RIGHT: u32 = 2;
+const DOWN: u32 = 4;
+const LEFT: u32 = 6;
+
+/// Build an arms mask from up, right, down and left weights
+const fn arms(up: u8, right: u8, down: u8, left: u8) -> u8 {
+    up << UP | right << RIGHT | down << DOWN | left << LEFT
+}
+
+/// Get the weight of one arm of an arms mask
+fn arm(mask: u8, direction: u32) -> u8 {
+    (mask >> direction) & 3
+}
+
+/// Box-drawing characters and their arms. Where several characters have the
+/// same arms, the first one is used when encoding
+const BOX_CHARS: &[(char, u8)] = &[
+    // Light and heavy lines
+    ('\u{2500}', arms(NONE, LIGHT, NONE, LIGHT)),
+    ('\u{2501}', arms(NONE, HEAVY, NONE, HEAVY)),
+    ('\u{2502}', arms(LIGHT, NONE, LIGHT, NONE)),
+    ('\u{2503}', arms(HEAVY, NONE, HEAVY, NONE)),
+    ('\u{250C}', arms(NONE, LIGHT, LIGHT, NONE)),
+    ('\u{250D}', arms(NONE, HEAVY, LIGHT, NONE)),
+    ('\u{250E}', arms(NONE, LIGHT, HEAVY, NONE)),
+    ('\u{250F}', arms(NONE, HEAVY, HEAVY, NONE)),
+    ('\u{2510}', arms(NONE, NONE, LIGHT, LIGHT)),
+    ('\u{2511}', arms(NONE, NONE, LIGHT, HEAVY)),
+    ('\u{2512}', arms(NONE, NONE, HEAVY, LIGHT)),
+    ('\u{2513}', arms(NONE, NONE, HEAVY, HEAVY)),
+    ('\u{2514}', arms(LIGHT, LIGHT, NONE, NONE)),
+    ('\u{2515}', arms(LIGHT, HEAVY, NONE, NONE)),
+    ('\u{2516}', arms(HEAVY, LIGHT, NONE, NONE)),
+    ('\u{2517}', arms(HEAVY, HEAVY, NONE, NONE)),
+    ('\u{2518}', arms(LIGHT, NONE, NONE, LIGHT)),
+    ('\u{2519}', arms(LIGHT, NONE, NONE, HEAVY)),
+    ('\u{251A}', arms(HEAVY, NONE, NONE, LIGHT)),
+    ('\u{251B}', arms(HEAVY, NONE, NONE, HEAVY)),
+    ('\u{251C}', arms(LIGHT, LIGHT, LIGHT, NONE)),
+    ('\u{251D}', arms(LIGHT, HEAVY, LIGHT, NONE)),
+    ('\u{251E}', arms(HEAVY, LIGHT, LIGHT, NONE)),
+    ('\u{251F}', arms(LIGHT, LIGHT, HEAVY, NONE)),
+    ('\u{2520}', arms(HEAVY, LIGHT, HEAVY, NONE)),
+    ('\u{2521}', arms(HEAVY, HEAVY, LIGHT, NONE)),
+    ('\u{2522}', arms(LIGHT, HEAVY, HEAVY, NONE)),
+    ('\u{2523}', arms(HEAVY, HEAVY, HEAVY, NONE)),
+    ('\u{2524}', arms(LIGHT, NONE, LIGHT, LIGHT)),
+    ('\u{2525}', arms(LIGHT, NONE, LIGHT, HEAVY)),
+    ('\u{2526}', arms(HEAVY, NONE, LIGHT, LIGHT)),
+    ('\u{2527}', arms(LIGHT, NONE, HEAVY, LIGHT)),
+    ('\u{2528}', arms(HEAVY, NONE, HEAVY, LIGHT)),
+    ('\u{2529}', arms(HEAVY, NONE, LIGHT, HEAVY)),
+    ('\u{252A}', arms(LIGHT, NONE, HEAVY, HEAVY)),
+    ('\u{252B}', arms(HEAVY, NONE, HEAVY, HEAVY)),
+    ('\u{252C}', arms(NONE, LIGHT, LIGHT, LIGHT)),
+    ('\u{252D}', arms(NONE, LIGHT, LIGHT, HEAVY)),
+    ('\u{252E}', arms(NONE, HEAVY, LIGHT, LIGHT)),
+    ('\u{252F}', arms(NONE, HEAVY, LIGHT, HEAVY)),
+    ('\u{2530}', arms(NONE, LIGHT, HEAVY, LIGHT)),
+    ('\u{2531}', arms(NONE, LIGHT, HEAVY, HEAVY)),
+    ('\u{2532}', arms(NONE, HEAVY, HEAVY, LIGHT)),
+    ('\u{2533}', arms(NONE, HEAVY, HEAVY, HEAVY)),
+    ('\u{2534}', arms(LIGHT, LIGHT, NONE, LIGHT)),
+    ('\u{2535}', arms(LIGHT, LIGHT, NONE, HEAVY)),
+    ('\u{2536}', arms(LIGHT, HEAVY, NONE, LIGHT)),
+    ('\u{2537}', arms(LIGHT, HEAVY, NONE, HEAVY)),
+    ('\u{2538}', arms(HEAVY, LIGHT, NONE, LIGHT)),
+    ('\u{2539}', arms(HEAVY, LIGHT, NONE, HEAVY)),
+    ('\u{253A}', arms(HEAVY, HEAVY, NONE, LIGHT)),
+    ('\u{253B}', arms(HEAVY, HEAVY, NONE, HEAVY)),
+    ('\u{253C}', arms(LIGHT, LIGHT, LIGHT, LIGHT)),
+    ('\u{253D}', arms(LIGHT, LIGHT, LIGHT, HEAVY)),
+    ('\u{253E}', arms(LIGHT, HEAVY, LIGHT, LIGHT)),
+    ('\u{253F}', arms(LIGHT, HEAVY, LIGHT, HEAVY)),
+    ('\u{2540}', arms(HEAVY, LIGHT, LIGHT, LIGHT)),
+    ('\u{2541}', arms(LIGHT, LIGHT, HEAVY, LIGHT)),
+    ('\u{2542}', arms(HEAVY, LIGHT, HEAVY, LIGHT)),
+    ('\u{2543}', arms(HEAVY, LIGHT, LIGHT, HEAVY)),
+    ('\u{2544}', arms(HEAVY, HEAVY, LIGHT, LIGHT)),
+    ('\u{2545}', arms(LIGHT, LIGHT, HEAVY, HEAVY)),
+    ('\u{2546}', arms(LIGHT, HEAVY, HEAVY, LIGHT)),
+    ('\u{2547}', arms(HEAVY, HEAVY, LIGHT, HEAVY)),
+    ('\u{2548}', arms(LIGHT, HEAVY, HEAVY, HEAVY)),
+    ('\u{2549}', arms(HEAVY, LIGHT, HEAVY, HEAVY)),
+    ('\u{254A}', arms(HEAVY, HEAVY, HEAVY, LIGHT)),
+    ('\u{254B}', arms(HEAVY, HEAVY, HEAVY, HEAVY)),
+    // Double lines, and junctions of double and light lines
+    ('\u{2550}', arms(NONE, DOUBLE, NONE, DOUBLE)),
+    ('\u{2551}', arms(DOUBLE, NONE, DOUBLE, NONE)),
+    ('\u{2552}', arms(NONE, DOUBLE, LIGHT, NONE)),
+    ('\u{2553}', arms(NONE, LIGHT, DOUBLE, NONE)),
+    ('\u{2554}', arms(NONE, DOUBLE, DOUBLE, NONE)),
+    ('\u{2555}', arms(NONE, NONE, LIGHT, DOUBLE)),
+    ('\u{2556}', arms(NONE, NONE, DOUBLE, LIGHT)),
+    ('\u{2557}', arms(NONE, NONE, DOUBLE, DOUBLE)),
+    ('\u{2558}', arms(LIGHT, DOUBLE, NONE, NONE)),
+    ('\u{2559}', arms(DOUBLE, LIGHT, NONE, NONE)),
+    ('\u{255A}', arms(DOUBLE, DOUBLE, NONE, NONE)),
+    ('\u{255B}', arms(LIGHT, NONE, NONE, DOUBLE)),
+    ('\u{255C}', arms(DOUBLE, NONE, NONE, LIGHT)),
+    ('\u{255D}', arms(DOUBLE, NONE, NONE, DOUBLE)),
+    ('\u{255E}', arms(LIGHT, DOUBLE, LIGHT, NONE)),
+    ('\u{255F}', arms(DOUBLE, LIGHT, DOUBLE, NONE)),
+    ('\u{2560}', arms(DOUBLE, DOUBLE, DOUBLE, NONE)),
+    ('\u{2561}', arms(LIGHT, NONE, LIGHT, DOUBLE)),
+    ('\u{2562}', arms(DOUBLE, NONE, DOUBLE, LIGHT)),
+    ('\u{2563}', arms(DOUBLE, NONE, DOUBLE, DOUBLE)),
+    ('\u{2564}', arms(NONE, DOUBLE, LIGHT, DOUBLE)),
+    ('\u{2565}', arms(NONE, LIGHT, DOUBLE, LIGHT)),
+    ('\u{2566}', arms(NONE, DOUBLE, DOUBLE, DOUBLE)),
+    ('\u{2567}', arms(LIGHT, DOUBLE, NONE, DOUBLE)),
+    ('\u{2568}', arms(DOUBLE, LIGHT, NONE, LIGHT)),
+    ('\u{2569}', arms(DOUBLE, DOUBLE, NONE, DOUBLE)),
+    ('\u{256A}', arms(LIGHT, DOUBLE, LIGHT, DOUBLE)),
+    ('\u{256B}', arms(DOUBLE, LIGHT, DOUBLE, LIGHT)),
+    ('\u{256C}', arms(DOUBLE, DOUBLE, DOUBLE, DOUBLE)),
+    // Rounded corners, same arms as light corners
+    ('\u{256D}', arms(NONE, LIGHT, LIGHT, NONE)),
+    ('\u{256E}', arms(NONE, NONE, LIGHT, LIGHT)),
+    ('\u{256F}', arms(LIGHT, NONE, NONE, LIGHT)),
+    ('\u{2570}', arms(LIGHT, LIGHT, NONE, NONE)),
+    // Half lines
+    ('\u{2574}', arms(NONE, NONE, NONE, LIGHT)),
+    ('\u{2575}', arms(LIGHT, NONE, NONE, NONE)),
+    ('\u{2576}', arms(NONE, LIGHT, NONE, NONE)),
+    ('\u{2577}', arms(NONE, NONE, LIGHT, NONE)),
+    ('\u{2578}', arms(NONE, NONE, NONE, HEAVY)),
+    ('\u{2579}', arms(HEAVY, NONE, NONE, NONE)),
+    ('\u{257A}', arms(NONE, HEAVY, NONE, NONE)),
+    ('\u{257B}', arms(NONE, NONE, HEAVY, NONE)),
+    ('\u{257C}', arms(NONE, HEAVY, NONE, LIGHT)),
+    ('\u{257D}', arms(LIGHT, NONE, HEAVY, NONE)),
+    ('\u{257E}', arms(NONE, LIGHT, NONE, HEAVY)),
+    ('\u{257F}', arms(HEAVY, NONE, LIGHT, NONE)),
+];
+
+/// Get the arms of a box-drawing character, or `None` if it isn't one
+fn decode_box_char(character: char) -> Option<u8> {
+    BOX_CHARS.iter().find(|&&(box_char, _)| box_char == character).map(|&(_, mask)| mask)
+}
+
+/// Get the weights of the up, right, down and left arms of a box-drawing
+/// character: 0 for none, 1 for light, 2 for heavy and 3 for double
+pub(crate) fn box_char_arms(character: char) -> Option<[u8; 4]> {
+    let mask = decode_box_char(character)?;
+    Some([arm(mask, UP), arm(mask, RIGHT), arm(mask, DOWN), arm(mask, LEFT)])
+}
+
+/// Get the box-drawing character with the given arms, if there is one
+fn encode_box_char(mask: u8) -> Option<char> {
+    BOX_CHARS.iter().find(|&&(_, box_mask)| box_mask == mask).map(|&(box_char, _)| box_char)
+}
+
+/// Line style of box-drawing frames
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BoxStyle {
+    #[default]
+    Single,
+    Double,
+    /// Single lines with rounded corners
+    Rounded,
+    Heavy,
+}
+
+impl BoxStyle {
+    fn weight(&self) -> u8 {
+        match self {
+            BoxStyle::Single | BoxStyle::Rounded => LIGHT,
+            BoxStyle::Heavy => HEAVY,
+            BoxStyle::Double => DOUBLE,
+        }
+    }
+}
+
+/// A rectangle of cells
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect{x, y, width, height}
+    }
+}
+
+/// Horizontal alignment of text
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// How text is drawn by `Framebuffer::draw_text`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+    pub align: Align,
+    /// Wrap lines at word boundaries instead of clipping them
+    pub wrap: bool,
+}
+
+/// Columns between tab stops, as in terminals
+const TAB_WIDTH: usize = 8;
+
+/// Expand tabs in a line to spaces, up to the next tab stop, and drop other
+/// control characters, which terminals would act on instead of showing
+fn expand_controls(line: &str) -> String {
+    let (mut out, mut column) = (String::with_capacity(line.len()), 0);
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        }
+        else if !is_control(c) {
+            out.push(c);
+            column += char_width(c);
+        }
+    }
+    out
+}
+
+/// Split a line into rows that fit in `width` columns. Words are moved to
+/// the next row when they don't fit, and split if they are wider than a row.
+/// Spaces where a line is broken are dropped
+fn wrap_line(line: &str, width: usize) -> Vec<(String, usize)> {
+    let mut rows = Vec::new();
+    let (mut row, mut row_width) = (String::new(), 0);
+    let mut spaces = 0;
+    
+    for (i, word) in line.split(' ').enumerate() {
+        if i > 0 {
+            spaces += 1;
+        }
+        if word.is_empty() {
+            continue;
+        }
+        
+        let word_width = str_width(word);
+        if row_width + spaces + word_width <= width {
+            row.extend(std::iter::repeat_n(' ', spaces));
+            row.push_str(word);
+            row_width += spaces + word_width;
+            spaces = 0;
+            continue;
+        }
+        
+        if row_width > 0 {
+            rows.push((std::mem::take(&mut row), row_width));
+            row_width = 0;
+        }
+        spaces = 0;
+        
+        for c in word.chars() {
+            let c_width = char_width(c);
+            if c_width > width {
+                continue;
+            }
+            if row_width + c_width > width {
+                rows.push((std::mem::take(&mut row), row_width));
+                row_width = 0;
+            }
+            row.push(c);
+            row_width += c_width;
+        }
+    }
+    
+    rows.push((row, row_width));
+    rows
+}
+
+/// Clip a line to `width` columns. Wide characters that don't fit entirely
+/// are dropped
+fn clip_line(line: &str, width: usize) -> (String, usize) {
+    let (mut row, mut row_width) = (String::new(), 0);
+    for c in line.chars() {
+        let c_width = char_width(c);
+        if row_width + c_width > width {
+            break;
+        }
+        row.push(c);
+        row_width += c_width;
+    }
+    (row, row_width)
+}
+
+impl Framebuffer {
+    /// Fill a rectangle. Cells outside the framebuffer are clipped
+    pub fn fill_rect(&mut self, rect: Rect, character: Option<char>, fg_color: Option<Color>, bg_color: Option<Color>) {
+        let right = rect.x.saturating_add(rect.width).min(self.width);
+        let bottom = rect.y.saturating_add(rect.height).min(self.height);
+        for cell_y in rect.y..bottom {
+            for cell_x in rect.x..right {
+                self.set_cell(cell_x, cell_y, character, fg_color, bg_color);
+            }
+        }
+    }
+    
+    /// Draw the outline of a rectangle with a single character. Cells outside
+    /// the framebuffer are clipped
+    pub fn stroke_rect(&mut self, rect: Rect, character: Option<char>, fg_color: Option<Color>, bg_color: Option<Color>) {
+        if rect.width == 0 || rect.height == 0 {
+            return;
+        }
+        
+        let Rect{x, y, width, height} = rect;
+        let (right, bottom) = (x.saturating_add(width - 1), y.saturating_add(height - 1));
+        for cell_x in x..=right.min(self.width.saturating_sub(1)) {
+            self.set_cell(cell_x, y, character, fg_color, bg_color);
+            self.set_cell(cell_x, bottom, character, fg_color, bg_color);
+        }
+        for cell_y in y..=bottom.min(self.height.saturating_sub(1)) {
+            self.set_cell(x, cell_y, character, fg_color, bg_color);
+            self.set_cell(right, cell_y, character, fg_color, bg_color);
+        }
+    }
+    
+    /// Draw a line between two cells with Bresenham's algorithm. The line is
+    /// clipped to the framebuffer first, so lines far outside of it don't
+    /// take long to draw
+    pub fn draw_line(&mut self, from: (usize, usize), to: (usize, usize), character: Option<char>, fg_color: Option<Color>, bg_color: Option<Color>) {
+        let ((mut x, mut y), (end_x, end_y)) = match self.clip_segment(from, to) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        let (dx, dy) = ((end_x - x).abs(), -(end_y - y).abs());
+        let (step_x, step_y) = (if x < end_x { 1 } else { -1 }, if y < end_y { 1 } else { -1 });
+        let mut error = dx + dy;
+        
+        loop {
+            self.set_cell(x as usize, y as usize, character, fg_color, bg_color);
+            if x == end_x && y == end_y {
+                break;
+            }
+            
+            let error2 = error * 2;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    
+    /// Clip a line to the framebuffer cells with the Liang–Barsky algorithm,
+    /// returning its first and last cell, or `None` if it misses the
+    /// framebuffer
+    fn clip_segment(&self, from: (usize, usize), to: (usize, usize)) -> Option<((i64, i64), (i64, i64))> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        
+        // Lines inside are drawn exactly as given
+        let inside = |(x, y): (usize, usize)| x < self.width && y < self.height;
+        if inside(from) && inside(to) {
+            return Some(((from.0 as i64, from.1 as i64), (to.0 as i64, to.1 as i64)));
+        }
+        
+        let (x0, y0, x1, y1) = (from.0 as f64, from.1 as f64, to.0 as f64, to.1 as f64);
+        let (max_x, max_y) = ((self.width - 1) as f64, (self.height - 1) as f64);
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        for &(p, q) in &[(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+            if p == 0.0 {
+                // Parallel to this edge, and either inside or outside of it
+                if q < 0.0 {
+                    return None;
+                }
+            }
+            else if p < 0.0 {
+                enter = enter.max(q / p);
+            }
+            else {
+                exit = exit.min(q / p);
+            }
+        }
+        if enter > exit {
+            return None;
+        }
+        
+        // Rounding can land just outside, so clamp to the edge cells
+        let point = |t: f64| {
+            ((x0 + dx * t).round().clamp(0.0, max_x) as i64, (y0 + dy * t).round().clamp(0.0, max_y) as i64)
+        };
+        Some((point(enter), point(exit)))
+    }
+    
+    /// Add box-drawing arms to a cell, merging them with the box-drawing
+    /// character already there. Arms already in the cell are replaced by the
+    /// new arms, and kept otherwise
+    fn merge_box_arms(&mut self, x: usize, y: usize, new_arms: u8, style: BoxStyle, fg_color: Option<Color>, bg_color: Option<Color>) {
+        let existing = match self.get_cell(x, y) {
+            Some(cell) => cell.grapheme.base().and_then(decode_box_char).unwrap_or(0),
+            None => return,
+        };
+        
+        let mut mask = new_arms;
+        for &direction in &[UP, RIGHT, DOWN, LEFT] {
+            if arm(new_arms, direction) == NONE {
+                mask |= existing & (3 << direction);
+            }
+        }
+        
+        // Not every mix of weights has a character. Fall back to drawing all
+        // arms in the new style, then to ignoring the existing arms
+        let weight = style.weight();
+        let uniform = [UP, RIGHT, DOWN, LEFT].iter()
+            .filter(|&&direction| arm(mask, direction) != NONE)
+            .fold(0, |uniform, &direction| uniform | weight << direction);
+        let mut character = encode_box_char(mask)
+            .or_else(|| encode_box_char(uniform))
+            .or_else(|| encode_box_char(new_arms));
+        
+        // Light corners are drawn rounded in the rounded style
+        if style == BoxStyle::Rounded {
+            character = character.map(|c| match c {
+                '\u{250C}' => '\u{256D}',
+                '\u{2510}' => '\u{256E}',
+                '\u{2518}' => '\u{256F}',
+                '\u{2514}' => '\u{2570}',
+                c => c,
+            });
+        }
+        
+        self.set_cell(x, y, character, fg_color, bg_color);
+    }
+    
+    /// Check whether a cell holds a box-drawing character
+    fn is_box_cell(&self, x: usize, y: usize) -> bool {
+        self.get_cell(x, y).is_some_and(|cell| cell.grapheme.base().and_then(decode_box_char).is_some())
+    }
+    
+    /// Draw a horizontal box-drawing line. Junctions with box-drawing
+    /// characters already in the framebuffer are merged, so a line drawn
+    /// across a frame ends in tees instead of crossing it
+    pub fn draw_hline(&mut self, x: usize, y: usize, width: usize, style: BoxStyle, fg_color: Option<Color>, bg_color: Option<Color>) {
+        let weight = style.weight();
+        for cell_x in x..x + width {
+            // Ends only reach outwards if they aren't joining another line
+            let mut mask = 0;
+            if cell_x > x || !self.is_box_cell(cell_x, y) {
+                mask |= weight << LEFT;
+            }
+            if cell_x < x + width - 1 || !self.is_box_cell(cell_x, y) {
+                mask |= weight << RIGHT;
+            }
+            self.merge_box_arms(cell_x, y, mask, style, fg_color, bg_color);
+        }
+    }
+    
+    /// Draw a vertical box-drawing line. Junctions are merged, like in
+    /// `draw_hline`
+    pub fn draw_vline(&mut self, x: usize, y: usize, height: usize, style: BoxStyle, fg_color: Option<Color>, bg_color: Option<Color>) {
+        let weight = style.weight();
+        for cell_y in y..y + height {
+            let mut mask = 0;
+            if cell_y > y || !self.is_box_cell(x, cell_y) {
+                mask |= weight << UP;
+            }
+            if cell_y < y + height - 1 || !self.is_box_cell(x, cell_y) {
+                mask |= weight << DOWN;
+            }
+            self.merge_box_arms(x, cell_y, mask, style, fg_color, bg_color);
+        }
+    }
+    
+    /// Draw a box-drawing frame. Junctions with box-drawing characters already
+    /// in the framebuffer are merged, so adjacent or overlapping frames share
+    /// their borders. The inside of the frame is left untouched
+    pub fn draw_box(&mut self, rect: Rect, style: BoxStyle, fg_color: Option<Color>, bg_color: Option<Color>) {
+        if rect.width < 2 || rect.height < 2 {
+            return;
+        }
+        
+        let Rect{x, y, width, height} = rect;
+        let weight = style.weight();
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        for cell_x in x + 1..right {
+            self.merge_box_arms(cell_x, y, weight << LEFT | weight << RIGHT, style, fg_color, bg_color);
+            self.merge_box_arms(cell_x, bottom, weight << LEFT | weight << RIGHT, style, fg_color, bg_color);
+        }
+        for cell_y in y + 1..bottom {
+            self.merge_box_arms(x, cell_y, weight << UP | weight << DOWN, style, fg_color, bg_color);
+            self.merge_box_arms(right, cell_y, weight << UP | weight << DOWN, style, fg_color, bg_color);
+        }
+        self.merge_box_arms(x, y, weight << RIGHT | weight << DOWN, style, fg_color, bg_color);
+        self.merge_box_arms(right, y, weight << LEFT | weight << DOWN, style, fg_color, bg_color);
+        self.merge_box_arms(x, bottom, weight << RIGHT | weight << UP, style, fg_color, bg_color);
+        self.merge_box_arms(right, bottom, weight << LEFT | weight << UP, style, fg_color, bg_color);
+    }
+    
+    /// Draw text inside a rectangle. Lines are split at `\n`,
+    /// then wrapped or clipped to the rectangle's width, measured in terminal
+    /// columns. Rows that don't fit in the rectangle are dropped. Returns the
+    /// amount of rows drawn
+    ///
+    /// Tabs are expanded to the next multiple of 8 columns from the start of
+    /// the line, and other control characters are dropped
+    pub fn draw_text(&mut self, rect: Rect, text: &str, style: &TextStyle) -> usize {
+        let Rect{x, y, width, height} = rect;
+        let mut rows_drawn = 0;
+        for line in text.lines() {
+            let line = expand_controls(line);
+            let rows = if style.wrap { wrap_line(&line, width) } else { vec![clip_line(&line, width)] };
+            for (row, row_width) in rows {
+                if rows_drawn >= height {
+                    return rows_drawn;
+                }
+                
+                let offset = match style.align {
+                    Align::Left => 0,
+                    Align::Center => width.saturating_sub(row_width) / 2,
+                    Align::Right => width.saturating_sub(row_width),
+                };
+                
+                let (mut cell_x, cell_y) = (x + offset, y + rows_drawn);
+                for cluster in graphemes(&row) {
+                    self.set_grapheme(cell_x, cell_y, Some(cluster), style.fg, style.bg);
+                    if cell_x < self.width && cell_y < self.height {
+                        self.set_cell_style(cell_x, cell_y, None, None, Some(style.attributes));
+                    }
+                    cell_x += Grapheme::new(cluster).width();
+                }
+                rows_drawn += 1;
+            }
+        }
+        
+        rows_drawn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Get a row of the framebuffer as a string, with `|` for continuation
+    /// cells so that columns line up
+    fn row(framebuffer: &Framebuffer, y: usize) -> String {
+        (0..framebuffer.width).map(|x| {
+            let grapheme = framebuffer.get_cell(x, y).unwrap().grapheme;
+            if grapheme.is_continuation() { "|".to_string() } else { grapheme.to_string() }
+        }).collect()
+    }
+    
+    fn text(width: usize, height: usize, text: &str, wrap: bool) -> Vec<String> {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.draw_text(Rect::new(0, 0, width, height), text, &TextStyle{wrap, ..TextStyle::default()});
+        (0..height).map(|y| row(&framebuffer, y)).collect()
+    }
+    
+    #[test]
+    fn tabs_expand_to_tab_stops() {
+        assert_eq!(text(12, 1, "a\tb", false), ["a       b   "]);
+        assert_eq!(text(12, 1, "\t\tb", false), ["            "]);
+        assert_eq!(text(12, 1, "abcdefgh\tx", false), ["abcdefgh    "]);
+        assert_eq!(text(10, 2, "ab\tcd efg", true), ["ab      cd", "efg       "]);
+        // Columns count wide characters twice
+        assert_eq!(text(12, 1, "\u{754c}\u{754c}\u{754c}\tx", false), ["\u{754c}|\u{754c}|\u{754c}|  x   "]);
+    }
+    
+    #[test]
+    fn controls_are_dropped() {
+        assert_eq!(text(6, 1, "a\u{1b}[2Jb\u{7}\r\u{9b}c", false), ["a[2Jbc"]);
+    }
+    
+    #[test]
+    fn combining_marks() {
+        assert_eq!(text(4, 1, "e\u{301}e\u{301}xyz", false), ["e\u{301}e\u{301}xy"]);
+        assert_eq!(text(3, 2, "ab e\u{301}\u{302}f", true), ["ab ", "e\u{301}\u{302}f "]);
+        // A mark at the start of a line gets a space to sit on
+        assert_eq!(text(2, 1, "\u{301}a", false), [" \u{301}a"]);
+    }
+    
+    #[test]
+    fn wide_characters_at_the_clip_edge() {
+        assert_eq!(text(3, 1, "a\u{754c}\u{754c}", false), ["a\u{754c}|"]);
+        assert_eq!(text(4, 1, "a\u{754c}\u{754c}", false), ["a\u{754c}| "]);
+        assert_eq!(text(3, 2, "a\u{754c}\u{754c}", true), ["a\u{754c}|", "\u{754c}| "]);
+        
+        // Text clipped by the framebuffer rather than the rectangle
+        let mut framebuffer = Framebuffer::new(4, 1);
+        framebuffer.draw_text(Rect::new(1, 0, 8, 1), "ab\u{754c}", &TextStyle::default());
+        assert_eq!(row(&framebuffer, 0), " ab ");
+        framebuffer.draw_text(Rect::new(2, 0, 8, 1), "\u{754c}\u{754c}", &TextStyle::default());
+        assert_eq!(row(&framebuffer, 0), " a\u{754c}|");
+    }
+    
+    #[test]
+    fn wrapping_wide_characters() {
+        assert_eq!(text(5, 3, "ab \u{754c}\u{754c} c", true), ["ab   ", "\u{754c}|\u{754c}| ", "c    "]);
+        // Words wider than a row are split between wide characters
+        assert_eq!(text(3, 3, "\u{754c}\u{754c}\u{754c}", true), ["\u{754c}| ", "\u{754c}| ", "\u{754c}| "]);
+        // Wide characters wider than the whole row are dropped
+        assert_eq!(text(1, 2, "a\u{754c}b", true), ["a", "b"]);
+        
+        let mut framebuffer = Framebuffer::new(4, 1);
+        framebuffer.draw_text(Rect::new(0, 0, 4, 1), "a\u{754c}", &TextStyle{align: Align::Right, ..TextStyle::default()});
+        assert_eq!(row(&framebuffer, 0), " a\u{754c}|");
+    }
+    
+    /// Draw with a closure and return the rows of a width x height
+    /// framebuffer
+    fn drawn(width: usize, height: usize, draw: impl Fn(&mut Framebuffer)) -> Vec<String> {
+        let mut framebuffer = Framebuffer::new(width, height);
+        draw(&mut framebuffer);
+        (0..height).map(|y| row(&framebuffer, y)).collect()
+    }
+    
+    #[test]
+    fn crossing_lines() {
+        let rows = drawn(5, 5, |framebuffer| {
+            framebuffer.draw_hline(0, 2, 5, BoxStyle::Single, None, None);
+            framebuffer.draw_vline(2, 0, 5, BoxStyle::Single, None, None);
+        });
+        assert_eq!(rows, ["  \u{2502}  ", "  \u{2502}  ", "\u{2500}\u{2500}\u{253C}\u{2500}\u{2500}", "  \u{2502}  ", "  \u{2502}  "]);
+    }
+    
+    #[test]
+    fn lines_across_frames_end_in_tees() {
+        let rows = drawn(5, 3, |framebuffer| {
+            framebuffer.draw_box(Rect::new(0, 0, 5, 3), BoxStyle::Single, None, None);
+            framebuffer.draw_vline(2, 0, 3, BoxStyle::Single, None, None);
+        });
+        assert_eq!(rows, ["\u{250C}\u{2500}\u{252C}\u{2500}\u{2510}", "\u{2502} \u{2502} \u{2502}", "\u{2514}\u{2500}\u{2534}\u{2500}\u{2518}"]);
+        
+        let rows = drawn(5, 3, |framebuffer| {
+            framebuffer.draw_box(Rect::new(0, 0, 5, 3), BoxStyle::Single, None, None);
+            framebuffer.draw_hline(0, 1, 5, BoxStyle::Single, None, None);
+        });
+        assert_eq!(rows, ["\u{250C}\u{2500}\u{2500}\u{2500}\u{2510}", "\u{251C}\u{2500}\u{2500}\u{2500}\u{2524}", "\u{2514}\u{2500}\u{2500}\u{2500}\u{2518}"]);
+    }
+    
+    #[test]
+    fn adjacent_frames_share_borders() {
+        let rows = drawn(5, 3, |framebuffer| {
+            framebuffer.draw_box(Rect::new(0, 0, 3, 3), BoxStyle::Rounded, None, None);
+            framebuffer.draw_box(Rect::new(2, 0, 3, 3), BoxStyle::Rounded, None, None);
+        });
+        assert_eq!(rows, ["\u{256D}\u{2500}\u{252C}\u{2500}\u{256E}", "\u{2502} \u{2502} \u{2502}", "\u{2570}\u{2500}\u{2534}\u{2500}\u{256F}"]);
+    }
+    
+    #[test]
+    fn mixed_weight_junctions() {
+        // Heavy line across a light frame
+        let rows = drawn(4, 3, |framebuffer| {
+            framebuffer.draw_box(Rect::new(0, 0, 4, 3), BoxStyle::Single, None, None);
+            framebuffer.draw_hline(0, 1, 4, BoxStyle::Heavy, None, None);
+        });
+        assert_eq!(rows[1], "\u{251D}\u{2501}\u{2501}\u{2525}");
+        
+        // Light line across a double frame
+        let rows = drawn(4, 3, |framebuffer| {
+            framebuffer.draw_box(Rect::new(0, 0, 4, 3), BoxStyle::Double, None, None);
+            framebuffer.draw_hline(0, 1, 4, BoxStyle::Single, None, None);
+        });
+        assert_eq!(rows[1], "\u{255F}\u{2500}\u{2500}\u{2562}");
+        
+        // Double crossing light has a character of its own, double crossing
+        // heavy doesn't and is drawn all double
+        let cross = |first: BoxStyle, second: BoxStyle| drawn(3, 3, |framebuffer| {
+            framebuffer.draw_hline(0, 1, 3, first, None, None);
+            framebuffer.draw_vline(1, 0, 3, second, None, None);
+        })[1].chars().nth(1).unwrap();
+        assert_eq!(cross(BoxStyle::Single, BoxStyle::Double), '\u{256B}');
+        assert_eq!(cross(BoxStyle::Double, BoxStyle::Single), '\u{256A}');
+        assert_eq!(cross(BoxStyle::Single, BoxStyle::Heavy), '\u{2542}');
+        assert_eq!(cross(BoxStyle::Heavy, BoxStyle::Double), '\u{256C}');
+        assert_eq!(cross(BoxStyle::Double, BoxStyle::Heavy), '\u{254B}');
+    }
+    
+    #[test]
+    fn rects() {
+        let rows = drawn(6, 4, |framebuffer| {
+            framebuffer.stroke_rect(Rect::new(0, 0, 4, 3), Some('#'), None, None);
+            framebuffer.fill_rect(Rect::new(1, 1, 2, 1), Some('.'), None, None);
+            framebuffer.fill_rect(Rect::new(5, 0, 0, 4), Some('!'), None, None);
+            framebuffer.stroke_rect(Rect::new(4, 3, 1, 1), Some('o'), None, None);
+        });
+        assert_eq!(rows, ["####  ", "#..#  ", "####  ", "    o "]);
+    }
+    
+    #[test]
+    fn rects_are_clipped() {
+        let rows = drawn(4, 3, |framebuffer| {
+            framebuffer.stroke_rect(Rect::new(2, 1, 5, 5), Some('#'), None, None);
+            framebuffer.fill_rect(Rect::new(3, 2, 10, 10), Some('.'), None, None);
+        });
+        assert_eq!(rows, ["    ", "  ##", "  #."]);
+        
+        // Would take ages to walk, or overflow, without clipping
+        let rows = drawn(4, 3, |framebuffer| {
+            framebuffer.fill_rect(Rect::new(1, 2, usize::MAX, usize::MAX), Some('.'), None, None);
+            framebuffer.stroke_rect(Rect::new(0, 0, usize::MAX, usize::MAX), Some('#'), None, None);
+            framebuffer.stroke_rect(Rect::new(usize::MAX, 1, usize::MAX, 1), Some('!'), None, None);
+        });
+        assert_eq!(rows, ["####", "#   ", "#..."]);
+    }
+    
+    #[test]
+    fn lines() {
+        let rows = drawn(5, 4, |framebuffer| {
+            framebuffer.draw_line((0, 0), (4, 2), Some('\\'), None, None);
+            framebuffer.draw_line((4, 3), (0, 3), Some('-'), None, None);
+            framebuffer.draw_line((2, 2), (2, 2), Some('o'), None, None);
+        });
+        assert_eq!(rows, ["\\    ", " \\\\  ", "  o\\\\", "-----"]);
+    }
+    
+    #[test]
+    fn lines_are_clipped() {
+        let rows = drawn(4, 3, |framebuffer| {
+            framebuffer.draw_line((0, 1), (7, 1), Some('-'), None, None);
+            framebuffer.draw_line((6, 2), (2, 2), Some('_'), None, None);
+            // Would take ages to walk, or overflow, without clipping
+            framebuffer.draw_line((1, 0), (1, usize::MAX), Some('|'), None, None);
+            framebuffer.draw_line((usize::MAX, 0), (usize::MAX - 5, usize::MAX), Some('!'), None, None);
+            framebuffer.draw_line((1 << 40, 1 << 40), (0, 0), Some('\\'), None, None);
+        });
+        assert_eq!(rows, ["\\|  ", "-\\--", " |\\_"]);
+    }
+}
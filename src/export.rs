@@ -0,0 +1,705 @@
+//! Rasterize framebuffers into images, for screenshots and regression tests
+
+use crate::attributes::Attributes;
+use crate::color::Color;
+use crate::draw::box_char_arms;
+use crate::framebuffer::{Cell, Framebuffer, HALF_BLOCK};
+use crate::image::Image;
+
+/// Size of a glyph cell in pixels
+pub const GLYPH_WIDTH: usize = 8;
+pub const GLYPH_HEIGHT: usize = 16;
+
+/// 8x8 bitmap font for printable ASCII (U+0020 to U+007E). Each byte is a
+/// row, with the lowest bit as the leftmost pixel. Rows are doubled to fill
+/// a glyph cell
+const FONT_8X8: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // #
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // $
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // %
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // (
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // *
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ,
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // .
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // /
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // 0
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // 1
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // 2
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // 3
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // 4
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // 5
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // 6
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // 7
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // 8
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ;
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // <
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // =
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // >
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // ?
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // @
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // A
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // B
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // C
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // D
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // E
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // F
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // G
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // H
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // J
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // K
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // L
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // M
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // N
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // O
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // P
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // Q
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // R
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // S
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // V
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // W
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // X
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // Y
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // Z
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // [
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // backslash
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ]
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // _
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // a
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // b
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // c
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // d
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // e
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // f
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // g
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // h
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // j
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // k
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // l
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // m
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // o
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // p
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // q
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // r
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // s
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // v
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // w
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // y
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // z
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // }
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
+/// A glyph as a coverage mask. Each row is a byte with the lowest bit as the
+/// leftmost pixel
+type GlyphMask = [u8; GLYPH_HEIGHT];
+
+/// How cells are drawn when exporting a framebuffer to an image
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageStyle {
+    /// Each cell is a block of its background color. Half-block cells are
+    /// split into their top (foreground) and bottom (background) pixels
+    Blocks{cell_width: usize, cell_height: usize},
+    /// Each cell is an 8x16 glyph from a built-in bitmap font. The font
+    /// covers printable ASCII, block elements (shades, halves, quadrants),
+    /// Braille and box drawing. Other characters are drawn as a hollow box
+    Glyphs,
+}
+
+/// Fill rows `top..bottom` and columns `left..right` of a mask
+fn fill_mask(mask: &mut GlyphMask, left: usize, right: usize, top: usize, bottom: usize) {
+    let columns = ((1u16 << right) - (1u16 << left)) as u8;
+    for row in &mut mask[top..bottom] {
+        *row |= columns;
+    }
+}
+
+/// Get the mask of a block element (U+2580 to U+259F)
+fn block_mask(character: char) -> Option<GlyphMask> {
+    let (w, h) = (GLYPH_WIDTH, GLYPH_HEIGHT);
+    let mut mask = [0; GLYPH_HEIGHT];
+    match character as u32 {
+        // Upper half
+        0x2580 => fill_mask(&mut mask, 0, w, 0, h / 2),
+        // Lower eighths, up to the full block
+        code @ 0x2581..=0x2588 => {
+            let eighths = (code - 0x2580) as usize;
+            fill_mask(&mut mask, 0, w, h - eighths * h / 8, h);
+        },
+        // Left eighths, from 7/8 down to 1/8
+        code @ 0x2589..=0x258F => {
+            let eighths = (0x2590 - code) as usize;
+            fill_mask(&mut mask, 0, eighths * w / 8, 0, h);
+        },
+        // Right half
+        0x2590 => fill_mask(&mut mask, w / 2, w, 0, h),
+        // Light, medium and dark shades
+        code @ 0x2591..=0x2593 => {
+            for (y, row) in mask.iter_mut().enumerate() {
+                for x in 0..w {
+                    let set = match code {
+                        0x2591 => (x + y * 2) % 4 == 0,
+                        0x2592 => (x + y) % 2 == 0,
+                        _ => (x + y * 2) % 4 != 0,
+                    };
+                    if set {
+                        *row |= 1 << x;
+                    }
+                }
+            }
+        },
+        // Upper and right eighths
+        0x2594 => fill_mask(&mut mask, 0, w, 0, h / 8),
+        0x2595 => fill_mask(&mut mask, w - w / 8, w, 0, h),
+        // Quadrants, as upper left, upper right, lower left and lower right
+        code @ 0x2596..=0x259F => {
+            const QUADRANTS: [u8; 10] = [0b0100, 0b1000, 0b0001, 0b1101, 0b1001, 0b0111, 0b1011, 0b0010, 0b0110, 0b1110];
+            let quadrants = QUADRANTS[(code - 0x2596) as usize];
+            let areas = [(0, w / 2, 0, h / 2), (w / 2, w, 0, h / 2), (0, w / 2, h / 2, h), (w / 2, w, h / 2, h)];
+            for (i, &(left, right, top, bottom)) in areas.iter().enumerate() {
+                if quadrants & (1 << i) != 0 {
+                    fill_mask(&mut mask, left, right, top, bottom);
+                }
+            }
+        },
+        _ => return None,
+    }
+    Some(mask)
+}
+
+/// Get the mask of a Braille pattern (U+2800 to U+28FF)
+fn braille_mask(character: char) -> Option<GlyphMask> {
+    let code = character as u32;
+    if !(0x2800..=0x28FF).contains(&code) {
+        return None;
+    }
+    
+    // Dots 1-6 fill the left, then the right column of the first three rows,
+    // dots 7 and 8 the last row
+    const DOTS: [(usize, usize); 8] = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
+    let mut mask = [0; GLYPH_HEIGHT];
+    for (bit, &(column, row)) in DOTS.iter().enumerate() {
+        if code & (1 << bit) != 0 {
+            let (x, y) = (1 + column * 4, 1 + row * 4);
+            fill_mask(&mut mask, x, x + 2, y, y + 2);
+        }
+    }
+    Some(mask)
+}
+
+/// Get the mask of a box-drawing character (U+2500 to U+257F). Only the
+/// arms are drawn, so dashed and diagonal lines are not supported
+fn box_mask(character: char) -> Option<GlyphMask> {
+    let [up, right, down, left] = box_char_arms(character)?;
+    let (w, h) = (GLYPH_WIDTH, GLYPH_HEIGHT);
+    let (center_x, center_y) = (w / 2 - 1, h / 2 - 1);
+    let mut mask = [0; GLYPH_HEIGHT];
+    
+    // Offsets of the lines making up an arm of each weight, from the center
+    let lines = |weight: u8| -> &'static [usize] {
+        match weight {
+            1 => &[0],
+            2 => &[0, 1],
+            3 => &[0, 3],
+            _ => &[],
+        }
+    };
+    let double_offset = |weight: u8| if weight == 3 { 1 } else { 0 };
+    
+    for &offset in lines(up) {
+        let x = center_x + offset - double_offset(up);
+        fill_mask(&mut mask, x, x + 1, 0, center_y + 1);
+    }
+    for &offset in lines(down) {
+        let x = center_x + offset - double_offset(down);
+        fill_mask(&mut mask, x, x + 1, center_y, h);
+    }
+    for &offset in lines(left) {
+        let y = center_y + offset - double_offset(left);
+        fill_mask(&mut mask, 0, center_x + 1, y, y + 1);
+    }
+    for &offset in lines(right) {
+        let y = center_y + offset - double_offset(right);
+        fill_mask(&mut mask, center_x, w, y, y + 1);
+    }
+    Some(mask)
+}
+
+/// Get the mask of a printable ASCII character
+fn ascii_mask(character: char) -> Option<GlyphMask> {
+    let code = character as usize;
+    if !(0x20..0x7F).contains(&code) {
+        return None;
+    }
+    
+    let mut mask = [0; GLYPH_HEIGHT];
+    for (y, row) in mask.iter_mut().enumerate() {
+        *row = FONT_8X8[code - 0x20][y / 2];
+    }
+    Some(mask)
+}
+
+/// Get the mask of a character, or `None` if the font doesn't have it
+fn glyph_mask(character: char) -> Option<GlyphMask> {
+    ascii_mask(character)
+        .or_else(|| block_mask(character))
+        .or_else(|| braille_mask(character))
+        .or_else(|| box_mask(character))
+}
+
+/// Get the foreground and background colors a cell is shown with, after
+/// applying the reverse and dim attributes
+fn cell_colors(cell: &Cell) -> (Color, Color) {
+    let (mut fg, mut bg) = (cell.fg, cell.bg);
+    if cell.attributes.contains(Attributes::REVERSE) {
+        std::mem::swap(&mut fg, &mut bg);
+    }
+    if cell.attributes.contains(Attributes::DIM) {
+        fg = fg.mix(&bg, 0.5);
+    }
+    (fg, bg)
+}
+
+impl Framebuffer {
+    /// Rasterize the framebuffer, with its layers composited, into an image.
+    /// Colors are the framebuffer's colors, before they are matched to the
+    /// color mode
+    pub fn to_image(&self, style: ImageStyle) -> Image {
+        let (cell_width, cell_height) = match style {
+            ImageStyle::Blocks{cell_width, cell_height} => (cell_width, cell_height),
+            ImageStyle::Glyphs => (GLYPH_WIDTH, GLYPH_HEIGHT),
+        };
+        let black = Color{r: 0, g: 0, b: 0};
+        let mut image = Image::new(self.width * cell_width, self.height * cell_height, black);
+        
+        let cells = self.composite();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (_, bg) = cell_colors(&cells[y * self.width + x]);
+                image.fill_rect(x * cell_width, y * cell_height, cell_width, cell_height, bg);
+            }
+        }
+        
+        // Glyphs go over every background, as wide characters reach into
+        // the next cell
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let cell = &cells[y * self.width + x];
+                let (left, top) = (x * cell_width, y * cell_height);
+                let (fg, _) = cell_colors(cell);
+                
+                match style {
+                    ImageStyle::Blocks{..} => {
+                        if cell.grapheme == HALF_BLOCK {
+                            image.fill_rect(left, top, cell_width, cell_height / 2, fg);
+                        }
+                    },
+                    ImageStyle::Glyphs => Framebuffer::draw_glyph(&mut image, cell, left, top, fg),
+                }
+            }
+        }
+        
+        image
+    }
+    
+    /// Draw a cell's glyph and line attributes over its background
+    fn draw_glyph(image: &mut Image, cell: &Cell, left: usize, top: usize, fg: Color) {
+        let width = cell.grapheme.width();
+        let base = match cell.grapheme.base() {
+            Some(base) => base,
+            // Continuation cells are drawn by their wide character
+            None => return,
+        };
+        
+        let mut mask = match glyph_mask(base) {
+            Some(mask) if width == 1 => mask,
+            _ => {
+                // Hollow box, spanning both cells of wide characters
+                let right = GLYPH_WIDTH * width - 1;
+                for y in 2..GLYPH_HEIGHT - 2 {
+                    image.set_pixel(left + 1, top + y, fg);
+                    image.set_pixel(left + right - 1, top + y, fg);
+                }
+                for x in 1..right {
+                    image.set_pixel(left + x, top + 2, fg);
+                    image.set_pixel(left + x, top + GLYPH_HEIGHT - 3, fg);
+                }
+                [0; GLYPH_HEIGHT]
+            },
+        };
+        
+        if cell.attributes.contains(Attributes::BOLD) && base.is_ascii_graphic() {
+            for row in mask.iter_mut() {
+                *row |= *row << 1;
+            }
+        }
+        if cell.attributes.contains(Attributes::UNDERLINE) {
+            mask[GLYPH_HEIGHT - 1] = 0xFF;
+        }
+        if cell.attributes.contains(Attributes::STRIKETHROUGH) {
+            mask[GLYPH_HEIGHT / 2] = 0xFF;
+        }
+        
+        for (y, row) in mask.iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                if row & (1 << x) != 0 {
+                    image.set_pixel(left + x, top + y, fg);
+                }
+            }
+        }
+    }
+}
+
+/// Format a color as `#rrggbb`
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Escape text for HTML and SVG
+fn escape_into(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Get the CSS font and decoration properties for a cell's attributes
+fn font_style(attributes: Attributes, svg: bool) -> String {
+    let mut style = String::new();
+    if attributes.contains(Attributes::BOLD) {
+        style.push_str(if svg { " font-weight=\"bold\"" } else { ";font-weight:bold" });
+    }
+    if attributes.contains(Attributes::ITALIC) {
+        style.push_str(if svg { " font-style=\"italic\"" } else { ";font-style:italic" });
+    }
+    let underline = attributes.contains(Attributes::UNDERLINE);
+    let strikethrough = attributes.contains(Attributes::STRIKETHROUGH);
+    if underline || strikethrough {
+        let decoration = match (underline, strikethrough) {
+            (true, true) => "underline line-through",
+            (true, false) => "underline",
+            _ => "line-through",
+        };
+        if svg {
+            style.push_str(&format!(" text-decoration=\"{}\"", decoration));
+        }
+        else {
+            style.push_str(&format!(";text-decoration:{}", decoration));
+        }
+    }
+    style
+}
+
+impl Framebuffer {
+    /// Export the framebuffer, with its layers composited, as a standalone
+    /// HTML page. Cells are put in a `<pre>`, with runs of cells sharing
+    /// colors and attributes merged into one `<span>`. Colors are the
+    /// framebuffer's full RGB colors, not the ones sent to the console
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str("<style>pre { font-family: monospace; line-height: 1; display: inline-block; }</style>\n");
+        html.push_str("</head>\n<body>\n<pre>");
+        
+        let cells = self.composite();
+        for row in cells.chunks(self.width.max(1)).take(self.height) {
+            let mut run: Option<(Color, Color, Attributes)> = None;
+            for cell in row {
+                // Wide characters already cover their continuation cell
+                if cell.grapheme.is_continuation() {
+                    continue;
+                }
+                
+                let (fg, bg) = cell_colors(cell);
+                let style = (fg, bg, cell.attributes);
+                if run != Some(style) {
+                    if run.is_some() {
+                        html.push_str("</span>");
+                    }
+                    html.push_str(&format!("<span style=\"color:{};background:{}{}\">", hex(fg), hex(bg), font_style(cell.attributes, false)));
+                    run = Some(style);
+                }
+                escape_into(&mut html, cell.grapheme.as_str());
+            }
+            if run.is_some() {
+                html.push_str("</span>");
+            }
+            html.push('\n');
+        }
+        
+        html.push_str("</pre>\n</body>\n</html>\n");
+        html
+    }
+    
+    /// Export the framebuffer, with its layers composited, as a standalone
+    /// SVG image with one 8x16 unit cell per framebuffer cell. Backgrounds are
+    /// drawn as rects and characters as text, with runs of cells sharing
+    /// colors merged. Half-block cells are drawn as two rects. Colors are the
+    /// framebuffer's full RGB colors, not the ones sent to the console
+    pub fn to_svg(&self) -> String {
+        let (cell_width, cell_height) = (GLYPH_WIDTH, GLYPH_HEIGHT);
+        let (width, height) = (self.width * cell_width, self.height * cell_height);
+        let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", width, height);
+        svg.push_str("<g font-family=\"monospace\" font-size=\"14\">\n");
+        
+        let cells = self.composite();
+        for (y, row) in cells.chunks(self.width.max(1)).take(self.height).enumerate() {
+            let top = y * cell_height;
+            
+            // Backgrounds, then the top halves of half-block cells
+            for half_block in [false, true] {
+                let mut x = 0;
+                while x < row.len() {
+                    let color = |cell: &Cell| {
+                        let (fg, bg) = cell_colors(cell);
+                        match half_block {
+                            false => Some(bg),
+                            true if cell.grapheme == HALF_BLOCK => Some(fg),
+                            true => None,
+                        }
+                    };
+                    let start = x;
+                    let run_color = color(&row[x]);
+                    while x < row.len() && color(&row[x]) == run_color {
+                        x += 1;
+                    }
+                    if let Some(run_color) = run_color {
+                        let rect_height = if half_block { cell_height / 2 } else { cell_height };
+                        svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n", start * cell_width, top, (x - start) * cell_width, rect_height, hex(run_color)));
+                    }
+                }
+            }
+            
+            // Text, in runs of visible characters with the same style. Every
+            // character is placed at its cell's position
+            let mut x = 0;
+            while x < row.len() {
+                let cell = &row[x];
+                let visible = |cell: &Cell| !cell.grapheme.is_continuation() && cell.grapheme != ' ' && cell.grapheme != HALF_BLOCK;
+                if !visible(cell) {
+                    x += 1;
+                    continue;
+                }
+                
+                let (fg, _) = cell_colors(cell);
+                let attributes = cell.attributes;
+                let mut positions = Vec::new();
+                let mut text = String::new();
+                while x < row.len() {
+                    let cell = &row[x];
+                    if cell.grapheme.is_continuation() {
+                        x += 1;
+                        continue;
+                    }
+                    if !visible(cell) || cell_colors(cell).0 != fg || cell.attributes != attributes {
+                        break;
+                    }
+                    for c in cell.grapheme.as_str().chars() {
+                        positions.push((x * cell_width).to_string());
+                        escape_into(&mut text, c.encode_utf8(&mut [0; 4]));
+                    }
+                    x += 1;
+                }
+                svg.push_str(&format!("<text x=\"{}\" y=\"{}\" fill=\"{}\"{}>{}</text>\n", positions.join(" "), top + cell_height - 4, hex(fg), font_style(attributes, true), text));
+            }
+        }
+        
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const WHITE: Color = Color{r: 255, g: 255, b: 255};
+    const BLACK: Color = Color{r: 0, g: 0, b: 0};
+    const RED: Color = Color{r: 255, g: 0, b: 0};
+    const BLUE: Color = Color{r: 0, g: 0, b: 255};
+    
+    /// Get the pixels of a glyph cell, `#` where they have the color `fg`
+    fn glyph(image: &Image, x: usize, fg: Color) -> Vec<String> {
+        (0..GLYPH_HEIGHT).map(|y| {
+            (0..GLYPH_WIDTH).map(|pixel_x| if image.get_pixel(x * GLYPH_WIDTH + pixel_x, y) == Some(fg) { '#' } else { '.' }).collect()
+        }).collect()
+    }
+    
+    /// Render a single character as a glyph, white on black
+    fn render(character: char, attributes: Attributes) -> Vec<String> {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.set_styled_cell(0, 0, Some(character), Some(WHITE), Some(BLACK), Some(attributes));
+        glyph(&framebuffer.to_image(ImageStyle::Glyphs), 0, WHITE)
+    }
+    
+    #[test]
+    fn blocks() {
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.set_cell(0, 0, Some('x'), Some(WHITE), Some(RED));
+        framebuffer.set_cell(1, 0, Some(HALF_BLOCK), Some(WHITE), Some(BLUE));
+        let image = framebuffer.to_image(ImageStyle::Blocks{cell_width: 2, cell_height: 4});
+        assert_eq!((image.width, image.height), (4, 4));
+        
+        // Characters other than the half block only show their background,
+        // half blocks show the foreground on top
+        let rows: Vec<Vec<Color>> = (0..4).map(|y| (0..4).map(|x| image.get_pixel(x, y).unwrap()).collect()).collect();
+        assert_eq!(rows, [
+            [RED, RED, WHITE, WHITE],
+            [RED, RED, WHITE, WHITE],
+            [RED, RED, BLUE, BLUE],
+            [RED, RED, BLUE, BLUE],
+        ]);
+        
+        framebuffer.set_styled_cell(0, 0, None, None, None, Some(Attributes::REVERSE));
+        let image = framebuffer.to_image(ImageStyle::Blocks{cell_width: 1, cell_height: 1});
+        assert_eq!(image.get_pixel(0, 0), Some(WHITE));
+    }
+    
+    #[test]
+    fn font_glyphs() {
+        let a = render('A', Attributes::NONE);
+        // Font rows are doubled
+        assert_eq!(a[..6], ["..##....", "..##....", ".####...", ".####...", "##..##..", "##..##.."]);
+        assert_eq!(a[14..], ["........", "........"]);
+        
+        let bold = render('A', Attributes::BOLD);
+        assert_eq!(bold[..3], ["..###...", "..###...", ".#####.."]);
+        let underlined = render('A', Attributes::UNDERLINE);
+        assert_eq!(underlined[15], "########");
+        let struck = render(' ', Attributes::STRIKETHROUGH);
+        assert_eq!(struck.iter().filter(|row| row.contains('#')).collect::<Vec<_>>(), ["########"]);
+    }
+    
+    #[test]
+    fn block_glyphs() {
+        assert!(render('\u{2588}', Attributes::NONE).iter().all(|row| row == "########"));
+        let upper = render('\u{2580}', Attributes::NONE);
+        assert!(upper[..8].iter().all(|row| row == "########") && upper[8..].iter().all(|row| row == "........"));
+        let left = render('\u{258C}', Attributes::NONE);
+        assert!(left.iter().all(|row| row == "####...."));
+        let quadrants = render('\u{259A}', Attributes::NONE);
+        assert_eq!((quadrants[0].as_str(), quadrants[15].as_str()), ("####....", "....####"));
+        assert_eq!(render('\u{2592}', Attributes::NONE)[..2], ["#.#.#.#.", ".#.#.#.#"]);
+    }
+    
+    #[test]
+    fn braille_and_box_glyphs() {
+        // Dots 1 and 8, top left and bottom right
+        let braille = render('\u{2881}', Attributes::NONE);
+        assert_eq!(braille[..4], ["........", ".##.....", ".##.....", "........"]);
+        assert_eq!(braille[12..], ["........", ".....##.", ".....##.", "........"]);
+        
+        let cross = render('\u{253C}', Attributes::NONE);
+        assert_eq!(cross[0], "...#....");
+        assert_eq!(cross[7], "########");
+        assert_eq!(cross[15], "...#....");
+        let double = render('\u{2550}', Attributes::NONE);
+        assert_eq!(double[6..10], ["########", "........", "........", "########"]);
+    }
+    
+    #[test]
+    fn missing_glyphs_are_hollow_boxes() {
+        let mut expected = vec!["........".to_string(); GLYPH_HEIGHT];
+        expected[2] = ".######.".to_string();
+        expected[13] = ".######.".to_string();
+        for row in &mut expected[3..13] {
+            *row = ".#....#.".to_string();
+        }
+        assert_eq!(render('\u{e9}', Attributes::NONE), expected);
+        
+        // Wide characters get one box over both cells
+        let mut framebuffer = Framebuffer::new(2, 1);
+        framebuffer.set_cell(0, 0, Some('\u{754c}'), Some(WHITE), Some(BLACK));
+        let image = framebuffer.to_image(ImageStyle::Glyphs);
+        let (left, right) = (glyph(&image, 0, WHITE), glyph(&image, 1, WHITE));
+        assert_eq!((left[2].as_str(), right[2].as_str()), (".#######", "#######."));
+        assert_eq!((left[8].as_str(), right[8].as_str()), (".#......", "......#."));
+    }
+    
+    #[test]
+    fn ppm() {
+        let mut image = Image::new(2, 2, BLACK);
+        image.set_pixel(0, 0, Color{r: 1, g: 2, b: 3});
+        image.set_pixel(1, 1, Color{r: 250, g: 251, b: 252});
+        let mut out = Vec::new();
+        image.write_ppm(&mut out).unwrap();
+        assert_eq!(out, b"P6\n2 2\n255\n\x01\x02\x03\x00\x00\x00\x00\x00\x00\xfa\xfb\xfc");
+    }
+    
+    /// Runs of styles, characters to escape, a wide character and a half
+    /// block
+    fn markup_scene() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.clear(' ', WHITE, BLACK);
+        framebuffer.set_cell(0, 0, Some('a'), None, None);
+        framebuffer.set_cell(1, 0, Some('<'), None, None);
+        framebuffer.set_cell(2, 0, Some('&'), Some(RED), None);
+        framebuffer.set_styled_cell(3, 0, Some('"'), Some(RED), None, Some(Attributes::BOLD));
+        framebuffer.set_cell(0, 1, Some('\u{754c}'), None, None);
+        framebuffer.set_cell(2, 1, Some(HALF_BLOCK), Some(RED), Some(BLUE));
+        framebuffer
+    }
+    
+    #[test]
+    fn html() {
+        let expected = concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<style>pre { font-family: monospace; line-height: 1; display: inline-block; }</style>\n",
+            "</head>\n<body>\n<pre>",
+            "<span style=\"color:#ffffff;background:#000000\">a&lt;</span>",
+            "<span style=\"color:#ff0000;background:#000000\">&amp;</span>",
+            "<span style=\"color:#ff0000;background:#000000;font-weight:bold\">&quot;</span>\n",
+            "<span style=\"color:#ffffff;background:#000000\">\u{754c}</span>",
+            "<span style=\"color:#ff0000;background:#0000ff\">\u{2580}</span>",
+            "<span style=\"color:#ffffff;background:#000000\"> </span>\n",
+            "</pre>\n</body>\n</html>\n",
+        );
+        assert_eq!(markup_scene().to_html(), expected);
+    }
+    
+    #[test]
+    fn svg() {
+        let expected = concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"32\" height=\"32\" viewBox=\"0 0 32 32\">\n",
+            "<g font-family=\"monospace\" font-size=\"14\">\n",
+            "<rect x=\"0\" y=\"0\" width=\"32\" height=\"16\" fill=\"#000000\"/>\n",
+            "<text x=\"0 8\" y=\"12\" fill=\"#ffffff\">a&lt;</text>\n",
+            "<text x=\"16\" y=\"12\" fill=\"#ff0000\">&amp;</text>\n",
+            "<text x=\"24\" y=\"12\" fill=\"#ff0000\" font-weight=\"bold\">&quot;</text>\n",
+            // The half block is a background rect and a rect for its top half
+            "<rect x=\"0\" y=\"16\" width=\"16\" height=\"16\" fill=\"#000000\"/>\n",
+            "<rect x=\"16\" y=\"16\" width=\"8\" height=\"16\" fill=\"#0000ff\"/>\n",
+            "<rect x=\"24\" y=\"16\" width=\"8\" height=\"16\" fill=\"#000000\"/>\n",
+            "<rect x=\"16\" y=\"16\" width=\"8\" height=\"8\" fill=\"#ff0000\"/>\n",
+            "<text x=\"0\" y=\"28\" fill=\"#ffffff\">\u{754c}</text>\n",
+            "</g>\n</svg>\n",
+        );
+        assert_eq!(markup_scene().to_svg(), expected);
+    }
+}
@@ -0,0 +1,1194 @@
+use crate::attributes::Attributes;
+use crate::color::{Color, ColorMetric};
+use crate::csi_color::{ColorMode, ConsoleColor, xterm256_index};
+use crate::dither::{Dither, dither_plane};
+use crate::layer::Layer;
+use crate::palette::Palette;
+use crate::unicode::{Grapheme, is_zero_width};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::string::{FromUtf8Error, String};
+use std::vec::Vec;
+
+/// Maximum amount of unchanged cells that are re-sent instead of moving the
+/// cursor over them. A cursor move costs at least 6 bytes
+const MAX_REPRINT_GAP: usize = 4;
+
+/// Glyph used to draw two pixels per cell in half-block mode. The foreground
+/// color is the top pixel and the background color the bottom pixel
+pub const HALF_BLOCK: char = '\u{2580}';
+
+/// Shade glyphs used for shade dithering, with how much of the cell they
+/// cover with the foreground color
+const SHADE_GLYPHS: [(char, f64); 3] = [('\u{2591}', 0.25), ('\u{2592}', 0.5), ('\u{2593}', 0.75)];
+
+/// A foreground/background pair mixed by a shade glyph, and the color it
+/// appears as
+#[derive(Copy, Clone, Debug)]
+struct ShadeMix {
+    character: char,
+    fg: usize,
+    bg: usize,
+    color: Color,
+}
+
+/// A framebuffer cell. A wide character takes up two cells: its own, and a
+/// continuation cell (`Grapheme::CONTINUATION`) to its right
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub grapheme: Grapheme,
+    pub fg: Color,
+    pub bg: Color,
+    pub attributes: Attributes,
+}
+
+/// A cell as it was last sent to the console
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct PresentedCell {
+    grapheme: Grapheme,
+    fg: ConsoleColor,
+    bg: ConsoleColor,
+    attributes: Attributes,
+}
+
+/// Error returned when presenting a frame
+#[derive(Debug)]
+pub enum PresentError {
+    /// Writing the frame failed
+    Io(io::Error),
+    /// The frame is not valid UTF-8. Only possible when converting to a
+    /// string
+    Utf8(FromUtf8Error),
+}
+
+impl fmt::Display for PresentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PresentError::Io(error) => write!(f, "could not write frame: {}", error),
+            PresentError::Utf8(error) => write!(f, "frame is not valid UTF-8: {}", error),
+        }
+    }
+}
+
+impl Error for PresentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PresentError::Io(error) => Some(error),
+            PresentError::Utf8(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for PresentError {
+    fn from(error: io::Error) -> PresentError {
+        PresentError::Io(error)
+    }
+}
+
+/// A framebuffer. Stores frame data such as dimensions and cells (colors
+/// and characters for each console cell)
+///
+/// Layers can be stacked over the cells. They are composited when
+/// presenting, so the cells themselves are never modified by them
+///
+/// Cells hold grapheme clusters. Wide characters cover the cell to their
+/// right with a continuation marker, and zero width characters are attached
+/// to the cluster in the cell they are set on
+///
+/// The last presented frame is remembered so that only changed cells are sent
+/// to the console. Use `force_repaint` if the console contents can no longer
+/// be trusted (for example, after other output). `resize` repaints by itself
+#[derive(Debug)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Cell>,
+    layers: Vec<Layer>,
+    composited: Vec<Cell>,
+    color_mode: ColorMode,
+    palette: Palette,
+    color_metric: ColorMetric,
+    color_cache: HashMap<(Color, bool), ConsoleColor>,
+    dither: Dither,
+    shade_dither: bool,
+    shade_mixes: Vec<ShadeMix>,
+    shade_mix_cache: HashMap<Color, usize>,
+    half_block: bool,
+    presented: Vec<PresentedCell>,
+    resolved: Vec<PresentedCell>,
+    full_repaint: bool,
+    out_buf: Vec<u8>,
+}
+
+impl Framebuffer {
+    /// Create a new framebuffer
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        let cells_cap = width * height;
+        let black = Color{r: 0, g: 0, b: 0};
+        
+        Framebuffer {
+            width,
+            height,
+            cells: vec![Cell{grapheme: Grapheme::default(), fg: black, bg: black, attributes: Attributes::NONE}; cells_cap],
+            layers: Vec::new(),
+            composited: Vec::new(),
+            color_mode: ColorMode::default(),
+            palette: Palette::default(),
+            color_metric: ColorMetric::default(),
+            color_cache: HashMap::new(),
+            dither: Dither::default(),
+            shade_dither: false,
+            shade_mixes: Vec::new(),
+            shade_mix_cache: HashMap::new(),
+            half_block: false,
+            presented: Vec::with_capacity(cells_cap),
+            resolved: Vec::with_capacity(cells_cap),
+            full_repaint: true,
+            out_buf: Vec::new(),
+        }
+    }
+    
+    /// Resize the framebuffer and its layers. Cells that are still in bounds
+    /// keep their content, new cells are blank. Wide characters cut off by the
+    /// new right edge are replaced by spaces. Forces a full repaint, as the
+    /// console has most likely reflowed its contents
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == self.width && height == self.height {
+            return;
+        }
+        
+        let black = Color{r: 0, g: 0, b: 0};
+        let mut cells = vec![Cell{grapheme: Grapheme::default(), fg: black, bg: black, attributes: Attributes::NONE}; width * height];
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                cells[y * width + x] = self.cells[y * self.width + x];
+            }
+        }
+        Framebuffer::repair_wide(&mut cells, width);
+        
+        self.cells = cells;
+        self.width = width;
+        self.height = height;
+        for layer in self.layers.iter_mut() {
+            layer.resize(width, height);
+        }
+        self.full_repaint = true;
+    }
+    
+    /// Clear the framebuffer with a character and foreground/background color.
+    /// Attributes are reset
+    pub fn clear(&mut self, clear_char: char, clear_fg_color: Color, clear_bg_color: Color) {
+        let clear_cell = Cell{grapheme: Grapheme::default(), fg: clear_fg_color, bg: clear_bg_color, attributes: Attributes::NONE};
+        for cell in self.cells.iter_mut() {
+            *cell = clear_cell;
+        }
+        
+        // Wide characters cover every other cell
+        let grapheme = Grapheme::from(clear_char);
+        for y in 0..self.height {
+            for x in (0..self.width).step_by(grapheme.width()) {
+                self.put_grapheme(x, y, grapheme);
+            }
+        }
+    }
+    
+    /// Set a cell's character, foreground and or background colors. A zero
+    /// width character, such as a combining mark, is attached to the cell's
+    /// grapheme cluster instead of replacing it
+    pub fn set_cell(&mut self, x: usize, y: usize, character: Option<char>, fg_color: Option<Color>, bg_color: Option<Color>) {
+        // Abort if out of bounds
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        
+        match character {
+            Some(character) if is_zero_width(character) => {
+                // Attach to the wide character this cell is covered by
+                let index = y * self.width + x;
+                let index = if self.cells[index].grapheme.is_continuation() { index - 1 } else { index };
+                self.cells[index].grapheme.push(character);
+            },
+            Some(character) => self.put_grapheme(x, y, Grapheme::from(character)),
+            None => {},
+        }
+        
+        self.set_cell_style(x, y, fg_color, bg_color, None);
+    }
+    
+    /// Set a cell's character, foreground and or background colors and or
+    /// attributes. Same as `set_cell`, but also writes the attributes
+    pub fn set_styled_cell(&mut self, x: usize, y: usize, character: Option<char>, fg_color: Option<Color>, bg_color: Option<Color>, attributes: Option<Attributes>) {
+        self.set_cell(x, y, character, fg_color, bg_color);
+        if x < self.width && y < self.height {
+            self.set_cell_style(x, y, None, None, attributes);
+        }
+    }
+    
+    /// Set a cell's grapheme cluster, foreground and or background colors
+    pub fn set_grapheme(&mut self, x: usize, y: usize, grapheme: Option<&str>, fg_color: Option<Color>, bg_color: Option<Color>) {
+        // Abort if out of bounds
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        
+        if let Some(grapheme) = grapheme {
+            self.put_grapheme(x, y, Grapheme::new(grapheme));
+        }
+        
+        self.set_cell_style(x, y, fg_color, bg_color, None);
+    }
+    
+    /// Set an in bounds cell's foreground and or background colors and or
+    /// attributes. The continuation cell of a wide character gets the same
+    /// style
+    pub(crate) fn set_cell_style(&mut self, x: usize, y: usize, fg_color: Option<Color>, bg_color: Option<Color>, attributes: Option<Attributes>) {
+        let index = y * self.width + x;
+        let cell = &mut self.cells[index];
+        
+        // Set foreground color
+        if let Some(color) = fg_color {
+            cell.fg = color;
+        }
+        
+        // Set background color
+        if let Some(color) = bg_color {
+            cell.bg = color;
+        }
+        
+        // Set attributes
+        if let Some(attributes) = attributes {
+            cell.attributes = attributes;
+        }
+        
+        let cell = *cell;
+        if cell.grapheme.width() == 2 {
+            let continuation = &mut self.cells[index + 1];
+            continuation.fg = cell.fg;
+            continuation.bg = cell.bg;
+            continuation.attributes = cell.attributes;
+        }
+    }
+    
+    /// Put a grapheme in an in bounds cell. Wide characters the cell was part
+    /// of are replaced by spaces, and a wide grapheme takes the cell to its
+    /// right as a continuation, or is replaced by a space in the last column
+    fn put_grapheme(&mut self, x: usize, y: usize, grapheme: Grapheme) {
+        let index = y * self.width + x;
+        self.break_wide(x, y);
+        
+        let mut grapheme = grapheme;
+        if grapheme.width() == 2 {
+            if x + 1 < self.width {
+                self.break_wide(x + 1, y);
+                self.cells[index + 1].grapheme = Grapheme::CONTINUATION;
+            }
+            else {
+                grapheme = Grapheme::default();
+            }
+        }
+        else if grapheme.is_continuation() {
+            // Continuations are only placed by their wide character
+            grapheme = Grapheme::default();
+        }
+        
+        self.cells[index].grapheme = grapheme;
+    }
+    
+    /// Replace the wide character covering an in bounds cell with spaces, as
+    /// the cell is about to be overwritten
+    fn break_wide(&mut self, x: usize, y: usize) {
+        let index = y * self.width + x;
+        let grapheme = self.cells[index].grapheme;
+        if grapheme.is_continuation() && x > 0 {
+            self.cells[index - 1].grapheme = Grapheme::default();
+            self.cells[index].grapheme = Grapheme::default();
+        }
+        else if grapheme.width() == 2 && x + 1 < self.width {
+            self.cells[index].grapheme = Grapheme::default();
+            self.cells[index + 1].grapheme = Grapheme::default();
+        }
+    }
+    
+    /// Scroll the cells up by one row. The bottom row is blanked with the
+    /// given colors
+    pub(crate) fn scroll_up(&mut self, fg_color: Color, bg_color: Color) {
+        if self.height == 0 {
+            return;
+        }
+        
+        self.cells.drain(..self.width);
+        let blank = Cell{grapheme: Grapheme::default(), fg: fg_color, bg: bg_color, attributes: Attributes::NONE};
+        self.cells.resize(self.width * self.height, blank);
+    }
+    
+    /// Get a cell, or `None` if out of bounds. Layers are not included
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<&Cell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        
+        self.cells.get(y * self.width + x)
+    }
+    
+    /// Add a transparent layer on top of the others, sized to the
+    /// framebuffer. If a layer with this name already exists, it is returned
+    /// instead
+    pub fn add_layer(&mut self, name: &str) -> &mut Layer {
+        let index = match self.layers.iter().position(|layer| layer.name == name) {
+            Some(index) => index,
+            None => {
+                self.layers.push(Layer::new(name, self.width, self.height));
+                self.layers.len() - 1
+            },
+        };
+        
+        &mut self.layers[index]
+    }
+    
+    /// Get a layer by name
+    pub fn layer(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+    
+    /// Get a layer by name, for drawing
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+    
+    /// Remove a layer by name
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer> {
+        let index = self.layers.iter().position(|layer| layer.name == name)?;
+        Some(self.layers.remove(index))
+    }
+    
+    /// Get the layers, from bottom to top
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+    
+    /// Get the layers, from bottom to top. Can be used to reorder them
+    pub fn layers_mut(&mut self) -> &mut Vec<Layer> {
+        &mut self.layers
+    }
+    
+    /// Composite the visible layers over the cells into `out`, which is
+    /// resized to the framebuffer
+    pub fn composite_into(&self, out: &mut Vec<Cell>) {
+        out.clear();
+        out.extend_from_slice(&self.cells);
+        
+        for layer in self.layers.iter().filter(|layer| layer.visible && layer.opacity > 0.0) {
+            for y in 0..self.height.min(layer.height()) {
+                for x in 0..self.width.min(layer.width()) {
+                    let layer_cell = match layer.get_cell(x, y) {
+                        Some(layer_cell) => layer_cell,
+                        None => continue,
+                    };
+                    let cell = &mut out[y * self.width + x];
+                    
+                    if let Some(bg) = layer_cell.bg {
+                        cell.bg = layer.blend_mode.blend(cell.bg, bg, layer.opacity);
+                    }
+                    
+                    // New characters are drawn over the background, as the
+                    // old character is gone
+                    let fg_below = if layer_cell.character.is_some() { cell.bg } else { cell.fg };
+                    if let Some(character) = layer_cell.character {
+                        cell.grapheme = Grapheme::from(character);
+                    }
+                    if let Some(fg) = layer_cell.fg {
+                        cell.fg = layer.blend_mode.blend(fg_below, fg, layer.opacity);
+                    }
+                    if let Some(attributes) = layer_cell.attributes {
+                        cell.attributes = attributes;
+                    }
+                    if cell.grapheme.width() == 2 && layer_cell.character.is_some() && x + 1 < self.width {
+                        out[y * self.width + x + 1].grapheme = Grapheme::CONTINUATION;
+                    }
+                }
+            }
+        }
+        
+        Framebuffer::repair_wide(out, self.width);
+    }
+    
+    /// Replace wide characters without a continuation cell, and continuation
+    /// cells without a wide character, with spaces. Layers can leave them
+    /// behind by drawing over half of a wide character
+    fn repair_wide(cells: &mut [Cell], width: usize) {
+        for row in cells.chunks_mut(width.max(1)) {
+            for x in 0..row.len() {
+                let grapheme = row[x].grapheme;
+                let broken = if grapheme.is_continuation() {
+                    x == 0 || row[x - 1].grapheme.width() != 2
+                }
+                else {
+                    grapheme.width() == 2 && (x + 1 == row.len() || !row[x + 1].grapheme.is_continuation())
+                };
+                if broken {
+                    row[x].grapheme = Grapheme::default();
+                }
+            }
+        }
+    }
+    
+    /// Get the cells with all visible layers composited over them, as they
+    /// will be presented
+    pub fn composite(&self) -> Vec<Cell> {
+        let mut out = Vec::with_capacity(self.cells.len());
+        self.composite_into(&mut out);
+        out
+    }
+    
+    /// Check whether half-block mode is enabled
+    pub fn half_block(&self) -> bool {
+        self.half_block
+    }
+    
+    /// Enable or disable half-block mode. This is a hint for renderers to
+    /// draw with `set_pixel` at double vertical resolution; pixels can be set
+    /// in either mode
+    pub fn set_half_block(&mut self, half_block: bool) {
+        self.half_block = half_block;
+    }
+    
+    /// Get the height of the pixel grid. Each cell holds two pixels stacked
+    /// vertically, so this is twice the height in cells
+    pub fn pixel_height(&self) -> usize {
+        self.height * 2
+    }
+    
+    /// Set a pixel of the width x (2 * height) pixel grid. The cell holding
+    /// the pixel is turned into a `HALF_BLOCK` cell, with the foreground color
+    /// as the top pixel and the background color as the bottom pixel
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        // Abort if out of bounds
+        if x >= self.width || y >= self.pixel_height() {
+            return;
+        }
+        
+        // The whole cell showed its background color before, so the other
+        // pixel keeps that color
+        let index = (y / 2) * self.width + x;
+        if self.cells[index].grapheme != HALF_BLOCK {
+            self.put_grapheme(x, y / 2, Grapheme::from(HALF_BLOCK));
+            self.cells[index].fg = self.cells[index].bg;
+        }
+        
+        let cell = &mut self.cells[index];
+        
+        if y % 2 == 0 {
+            cell.fg = color;
+        }
+        else {
+            cell.bg = color;
+        }
+    }
+    
+    /// Get a pixel of the width x (2 * height) pixel grid. Cells that are not
+    /// `HALF_BLOCK` cells are read as their background color
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.pixel_height() {
+            return None;
+        }
+        
+        let cell = &self.cells[(y / 2) * self.width + x];
+        if cell.grapheme == HALF_BLOCK && y % 2 == 0 {
+            Some(cell.fg)
+        }
+        else {
+            Some(cell.bg)
+        }
+    }
+    
+    /// Appends a number in decimal, without allocating
+    fn push_number(buf: &mut Vec<u8>, number: usize) {
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut rest = number;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (rest % 10) as u8;
+            rest /= 10;
+            if rest == 0 {
+                break;
+            }
+        }
+        buf.extend_from_slice(&digits[start..]);
+    }
+    
+    /// Moves console cursor
+    fn console_cursor(buf: &mut Vec<u8>, x: usize, y: usize) {
+        buf.extend_from_slice(b"\x1b[");
+        Framebuffer::push_number(buf, y + 1);
+        buf.extend_from_slice(b";");
+        Framebuffer::push_number(buf, x + 1);
+        buf.extend_from_slice(b"H");
+    }
+    
+    /// Clears the console
+    fn console_clear(buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"\x1b[2J");
+    }
+    
+    /// Pick nearest color from palette. Returns index, not code
+    fn pick_nearest_csi_color(palette: &Palette, target: &Color, metric: ColorMetric) -> usize {
+        let mut picked:usize = 0;
+        let mut picked_dist:f64 = metric.distance(&palette.colors[0], target);
+        
+        for (i, color) in palette.colors.iter().enumerate().skip(1) {
+            let dist = metric.distance(color, target);
+            if dist < picked_dist {
+                picked = i;
+                picked_dist = dist;
+            }
+        }
+        
+        picked
+    }
+    
+    /// Resolve a color to what will be sent to the console in the current
+    /// color mode. Results are cached, as perceptual metrics are expensive
+    fn resolve_color(&mut self, color: &Color, background: bool) -> ConsoleColor {
+        let (color_mode, palette, metric) = (self.color_mode, &self.palette, self.color_metric);
+        *self.color_cache.entry((*color, background)).or_insert_with(|| {
+            match color_mode {
+                ColorMode::Ansi16 => ConsoleColor::Csi(Framebuffer::pick_nearest_csi_color(palette, color, metric)),
+                ColorMode::Ansi256 => ConsoleColor::Indexed(xterm256_index(color, metric)),
+                ColorMode::TrueColor => ConsoleColor::Rgb(*color),
+                ColorMode::Monochrome => ConsoleColor::Default,
+            }
+        })
+    }
+    
+    /// Sets the console back/foreground color
+    fn console_color(buf: &mut Vec<u8>, color: ConsoleColor, background: bool, last: &mut ConsoleColor) {
+        // TODO Windows?
+        if *last == color {
+            return;
+        }
+        
+        // Set color if different than last color
+        *last = color;
+        buf.extend_from_slice(b"\x1b[");
+        match color {
+            ConsoleColor::Csi(index) => {
+                let code = if background { Palette::bg_code(index) } else { Palette::fg_code(index) };
+                Framebuffer::push_number(buf, code as usize);
+            },
+            ConsoleColor::Indexed(index) => {
+                buf.extend_from_slice(if background { b"48;5;" } else { b"38;5;" });
+                Framebuffer::push_number(buf, index as usize);
+            },
+            ConsoleColor::Rgb(color) => {
+                buf.extend_from_slice(if background { b"48;2;" } else { b"38;2;" });
+                Framebuffer::push_number(buf, color.r as usize);
+                buf.push(b';');
+                Framebuffer::push_number(buf, color.g as usize);
+                buf.push(b';');
+                Framebuffer::push_number(buf, color.b as usize);
+            },
+            ConsoleColor::Default => {
+                buf.extend_from_slice(if background { b"49" } else { b"39" });
+            },
+        }
+        buf.extend_from_slice(b"m");
+    }
+    
+    /// Sets the console text attributes. Only attributes that changed are
+    /// turned on or off
+    fn console_attributes(buf: &mut Vec<u8>, attributes: Attributes, last: &mut Attributes) {
+        if *last == attributes {
+            return;
+        }
+        
+        let turned_off = |attribute| last.contains(attribute) && !attributes.contains(attribute);
+        // Bold and dim are turned off together, so the one that stays on has
+        // to be turned on again
+        let bold_dim = Attributes::BOLD | Attributes::DIM;
+        let reset_bold_dim = turned_off(Attributes::BOLD) || turned_off(Attributes::DIM);
+        
+        buf.extend_from_slice(b"\x1b[");
+        let start = buf.len();
+        let mut last_off = 0;
+        for &(attribute, _, off) in &Attributes::SGR_CODES {
+            if turned_off(attribute) && off != last_off {
+                Framebuffer::push_sgr_code(buf, start, off);
+                last_off = off;
+            }
+        }
+        for &(attribute, on, _) in &Attributes::SGR_CODES {
+            let turned_on = !last.contains(attribute) || (reset_bold_dim && bold_dim.contains(attribute));
+            if attributes.contains(attribute) && turned_on {
+                Framebuffer::push_sgr_code(buf, start, on);
+            }
+        }
+        buf.extend_from_slice(b"m");
+        *last = attributes;
+    }
+    
+    /// Appends a code to an SGR sequence whose codes start at `start`
+    fn push_sgr_code(buf: &mut Vec<u8>, start: usize, code: u8) {
+        if buf.len() > start {
+            buf.push(b';');
+        }
+        Framebuffer::push_number(buf, code as usize);
+    }
+    
+    /// Resets console colors
+    fn console_reset(buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"\x1b[0m");
+    }
+    
+    /// Clears the console from the cursor to the end of the screen
+    fn console_clear_below(buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"\x1b[J");
+    }
+    
+    /// Stops console output
+    fn console_xoff(buf: &mut Vec<u8>) {
+        buf.push(0x13u8); // XOFF ASCII
+    }
+    
+    /// Continues console output
+    fn console_xon(buf: &mut Vec<u8>) {
+        buf.push(0x11u8); // XON ASCII
+    }
+    
+    /// Get the color mode used when presenting frames
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+    
+    /// Set the color mode used when presenting frames. Forces a full repaint
+    /// if the mode changed
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        if self.color_mode != color_mode {
+            self.color_mode = color_mode;
+            self.invalidate_colors();
+        }
+    }
+    
+    /// Get the 16 color palette the console is assumed to use
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+    
+    /// Set the 16 color palette the console is assumed to use. Nearest color
+    /// matching in 16 color mode uses these colors, so they should be the
+    /// colors the user actually sees
+    pub fn set_palette(&mut self, palette: Palette) {
+        if self.palette != palette {
+            self.palette = palette;
+            self.invalidate_colors();
+        }
+    }
+    
+    /// Get the metric used to pick the nearest console color
+    pub fn color_metric(&self) -> ColorMetric {
+        self.color_metric
+    }
+    
+    /// Set the metric used to pick the nearest console color. Forces a full
+    /// repaint if the metric changed
+    pub fn set_color_metric(&mut self, color_metric: ColorMetric) {
+        if self.color_metric != color_metric {
+            self.color_metric = color_metric;
+            self.invalidate_colors();
+        }
+    }
+    
+    /// Get the dithering used when quantizing to the 16 or 256 color palette
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+    
+    /// Set the dithering used when quantizing to the 16 or 256 color palette.
+    /// Has no effect in truecolor and monochrome modes
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+    }
+    
+    /// Check whether shade dithering is enabled
+    pub fn shade_dither(&self) -> bool {
+        self.shade_dither
+    }
+    
+    /// Enable or disable shade dithering. In 16 color mode, blank cells may
+    /// then be replaced by a shade glyph (`\u{2591}`, `\u{2592}`, `\u{2593}`)
+    /// mixing two palette colors, if that is closer to the cell's background
+    /// color. Needs a terminal that can display the shade glyphs
+    pub fn set_shade_dither(&mut self, shade_dither: bool) {
+        self.shade_dither = shade_dither;
+    }
+    
+    /// Drop cached color lookups and repaint everything. Needed whenever
+    /// the way colors are resolved changes
+    fn invalidate_colors(&mut self) {
+        self.color_cache.clear();
+        self.shade_mixes.clear();
+        self.shade_mix_cache.clear();
+        self.full_repaint = true;
+    }
+    
+    /// Forget the last presented frame so that the next frame is fully
+    /// redrawn, starting with a console clear
+    pub fn force_repaint(&mut self) {
+        self.full_repaint = true;
+    }
+    
+    /// Get the index of the palette mix (or plain background) nearest to a
+    /// color, for shade dithering
+    fn nearest_shade_mix(&mut self, color: &Color) -> usize {
+        if self.shade_mixes.is_empty() {
+            // Plain backgrounds first, so they win ties against mixes
+            let colors = self.palette.colors;
+            for (bg, bg_color) in colors.iter().enumerate() {
+                self.shade_mixes.push(ShadeMix{character: ' ', fg: bg, bg, color: *bg_color});
+            }
+            for (bg, bg_color) in colors.iter().enumerate() {
+                for (fg, fg_color) in colors.iter().enumerate() {
+                    if fg_color == bg_color {
+                        continue;
+                    }
+                    for &(character, coverage) in &SHADE_GLYPHS {
+                        self.shade_mixes.push(ShadeMix{character, fg, bg, color: bg_color.mix(fg_color, coverage)});
+                    }
+                }
+            }
+        }
+        
+        let (metric, mixes) = (self.color_metric, &self.shade_mixes);
+        *self.shade_mix_cache.entry(*color).or_insert_with(|| {
+            let mut picked = 0;
+            let mut picked_dist = f64::INFINITY;
+            for (i, mix) in mixes.iter().enumerate() {
+                let dist = metric.distance(&mix.color, color);
+                if dist < picked_dist {
+                    picked = i;
+                    picked_dist = dist;
+                }
+            }
+            picked
+        })
+    }
+    
+    /// Resolve the cells of this frame to palette colors with dithering. The
+    /// background plane is resolved first, as shade dithering also picks the
+    /// character and foreground of blank cells
+    fn resolve_cells_dithered(&mut self, source: &[Cell], cells: &mut Vec<PresentedCell>) {
+        let fg_colors: Vec<Color> = source.iter().map(|cell| cell.fg).collect();
+        let bg_colors: Vec<Color> = source.iter().map(|cell| cell.bg).collect();
+        cells.extend(source.iter().map(|cell| PresentedCell{
+            grapheme: cell.grapheme,
+            fg: ConsoleColor::Default,
+            bg: ConsoleColor::Default,
+            attributes: cell.attributes,
+        }));
+        
+        // Ordered dithering spread, about the distance between palette colors
+        let spread = if self.color_mode == ColorMode::Ansi16 { 96.0 } else { 40.0 };
+        let shade_dither = self.shade_dither && self.color_mode == ColorMode::Ansi16;
+        let (dither, width, height) = (self.dither, self.width, self.height);
+        
+        dither_plane(dither, width, height, &bg_colors, spread, |i, target| {
+            // Attributes such as reverse would change how the mix looks
+            if shade_dither && cells[i].grapheme == ' ' && cells[i].attributes.is_empty() {
+                let mix_index = self.nearest_shade_mix(&target);
+                let mix = self.shade_mixes[mix_index];
+                cells[i] = PresentedCell{
+                    grapheme: Grapheme::from(mix.character),
+                    fg: ConsoleColor::Csi(mix.fg),
+                    bg: ConsoleColor::Csi(mix.bg),
+                    attributes: Attributes::NONE,
+                };
+                return mix.color;
+            }
+            
+            cells[i].bg = self.resolve_color(&target, true);
+            cells[i].bg.to_color(&self.palette).unwrap()
+        });
+        
+        dither_plane(dither, width, height, &fg_colors, spread, |i, target| {
+            if source[i].grapheme == ' ' {
+                // The foreground of blank cells is invisible, so it should not
+                // diffuse error into its neighbours. Shade dithered cells
+                // already have a foreground
+                if cells[i].grapheme == ' ' {
+                    cells[i].fg = self.resolve_color(&target, false);
+                }
+                return target;
+            }
+            
+            cells[i].fg = self.resolve_color(&target, false);
+            cells[i].fg.to_color(&self.palette).unwrap()
+        });
+    }
+    
+    /// Resolve composited cells to the colors that will be sent to the
+    /// console
+    fn resolve_cells(&mut self, source: &[Cell], cells: &mut Vec<PresentedCell>) {
+        cells.clear();
+        let palette_mode = self.color_mode == ColorMode::Ansi16 || self.color_mode == ColorMode::Ansi256;
+        if palette_mode && (self.dither != Dither::None || self.shade_dither) {
+            self.resolve_cells_dithered(source, cells);
+            return;
+        }
+        
+        for cell in source {
+            cells.push(PresentedCell{
+                grapheme: cell.grapheme,
+                fg: self.resolve_color(&cell.fg, false),
+                bg: self.resolve_color(&cell.bg, true),
+                attributes: cell.attributes,
+            });
+        }
+    }
+    
+    /// Write a frame to `out`. Only the cells that changed since the last
+    /// presented frame are sent, unless a full repaint is needed. The cursor
+    /// is left at the start of the line below the frame. The frame is built
+    /// in a reusable buffer and sent with a single `write_all`
+    pub fn present(&mut self, out: &mut impl Write) -> Result<(), PresentError> {
+        self.encode_frame();
+        out.write_all(&self.out_buf)?;
+        out.flush()?;
+        Ok(())
+    }
+    
+    /// Render a frame to a string. Same as `present`, but allocates a new
+    /// string every frame
+    pub fn get_string(&mut self) -> Result<String, PresentError> {
+        self.encode_frame();
+        String::from_utf8(self.out_buf.clone()).map_err(PresentError::Utf8)
+    }
+    
+    /// Encode the next frame into the output buffer and remember it as the
+    /// last presented frame
+    fn encode_frame(&mut self) {
+        // Reuse buffers from the last frame
+        let mut buf = std::mem::take(&mut self.out_buf);
+        buf.clear();
+        let mut composited = std::mem::take(&mut self.composited);
+        self.composite_into(&mut composited);
+        let mut cells = std::mem::take(&mut self.resolved);
+        self.resolve_cells(&composited, &mut cells);
+        self.composited = composited;
+        let full_repaint = self.full_repaint || self.presented.len() != cells.len();
+        
+        Framebuffer::console_xoff(&mut buf);
+        Framebuffer::console_reset(&mut buf);
+        if full_repaint {
+            // Clear console
+            Framebuffer::console_clear(&mut buf);
+            Framebuffer::console_cursor(&mut buf, 0, 0);
+        }
+        
+        // Print changed cells. The cursor position is tracked so that cursor
+        // moves are only sent when skipping over unchanged cells. Wide
+        // characters print their continuation cell too, so those are skipped
+        let mut cursor: Option<(usize, usize)> = if full_repaint { Some((0, 0)) } else { None };
+        // Colors were just reset, so the console is using its default colors
+        let mut last_fg = ConsoleColor::Default;
+        let mut last_bg = ConsoleColor::Default;
+        let mut last_attributes = Attributes::NONE;
+        for y in 0..self.height {
+            let row = y * self.width;
+            for x in 0..self.width {
+                let width = cells[row + x].grapheme.width();
+                if width == 0 {
+                    continue;
+                }
+                if !full_repaint && cells[row + x..row + x + width] == self.presented[row + x..row + x + width] {
+                    continue;
+                }
+                
+                // Get the cursor to this cell. Short gaps on the same line are
+                // re-sent instead, as that is cheaper than moving the cursor
+                match cursor {
+                    Some((cursor_x, cursor_y)) if cursor_y == y && cursor_x <= x && x - cursor_x <= MAX_REPRINT_GAP => {
+                        for gap_cell in &cells[row + cursor_x..row + x] {
+                            Framebuffer::console_cell(&mut buf, gap_cell, &mut last_fg, &mut last_bg, &mut last_attributes);
+                        }
+                    },
+                    _ => Framebuffer::console_cursor(&mut buf, x, y),
+                }
+                
+                Framebuffer::console_cell(&mut buf, &cells[row + x], &mut last_fg, &mut last_bg, &mut last_attributes);
+                cursor = Some((x + width, y));
+            }
+        }
+        
+        // Reset colors and leave the cursor below the frame
+        Framebuffer::console_reset(&mut buf);
+        Framebuffer::console_cursor(&mut buf, 0, self.height);
+        Framebuffer::console_clear_below(&mut buf);
+        Framebuffer::console_xon(&mut buf);
+        
+        self.resolved = std::mem::replace(&mut self.presented, cells);
+        self.out_buf = buf;
+        self.full_repaint = false;
+    }
+    
+    /// Prints a single cell at the cursor position. Continuation cells print
+    /// nothing, as their wide character already covers them
+    fn console_cell(buf: &mut Vec<u8>, cell: &PresentedCell, last_fg: &mut ConsoleColor, last_bg: &mut ConsoleColor, last_attributes: &mut Attributes) {
+        if cell.grapheme.is_continuation() {
+            return;
+        }
+        
+        // Set attributes and colors
+        Framebuffer::console_attributes(buf, cell.attributes, last_attributes);
+        Framebuffer::console_color(buf, cell.fg, false, last_fg);
+        Framebuffer::console_color(buf, cell.bg, true, last_bg);
+        
+        // Set grapheme cluster
+        buf.extend_from_slice(cell.grapheme.as_str().as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{BlendMode, Rgba};
+    use crate::layer::LayerCell;
+    
+    const WHITE: Color = Color{r: 255, g: 255, b: 255};
+    const BLACK: Color = Color{r: 0, g: 0, b: 0};
+    /// Colors of a blank cell, set after the reset at the start of a frame
+    const BLANK_COLORS: &str = "\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m";
+    /// End of every frame: reset, cursor below the frame, clear below, XON
+    const FRAME_END: &str = "\x1b[0m\x1b[3;1H\x1b[J\x11";
+    
+    /// A blank 8x2 true color framebuffer, with its first frame presented
+    fn presented() -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(8, 2);
+        framebuffer.set_color_mode(ColorMode::TrueColor);
+        framebuffer.get_string().unwrap();
+        framebuffer
+    }
+    
+    #[test]
+    fn unchanged_frame() {
+        let mut framebuffer = presented();
+        assert_eq!(framebuffer.get_string().unwrap(), format!("\x13\x1b[0m{}", FRAME_END));
+    }
+    
+    #[test]
+    fn single_changed_cell() {
+        let mut framebuffer = presented();
+        framebuffer.set_cell(2, 1, Some('x'), Some(WHITE), Some(BLACK));
+        let expected = format!("\x13\x1b[0m\x1b[2;3H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0mx{}", FRAME_END);
+        assert_eq!(framebuffer.get_string().unwrap(), expected);
+    }
+    
+    #[test]
+    fn small_gap_is_reprinted() {
+        let mut framebuffer = presented();
+        framebuffer.set_cell(1, 0, Some('a'), Some(WHITE), Some(BLACK));
+        framebuffer.set_cell(4, 0, Some('b'), Some(WHITE), Some(BLACK));
+        let expected = format!(
+            "\x13\x1b[0m\x1b[1;2H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0ma\x1b[38;2;0;0;0m  \x1b[38;2;255;255;255mb{}",
+            FRAME_END,
+        );
+        assert_eq!(framebuffer.get_string().unwrap(), expected);
+    }
+    
+    #[test]
+    fn large_gap_moves_the_cursor() {
+        let mut framebuffer = presented();
+        framebuffer.set_cell(0, 0, Some('a'), Some(WHITE), Some(BLACK));
+        framebuffer.set_cell(1 + MAX_REPRINT_GAP + 1, 0, Some('b'), Some(WHITE), Some(BLACK));
+        let expected = format!("\x13\x1b[0m\x1b[1;1H\x1b[38;2;255;255;255m\x1b[48;2;0;0;0ma\x1b[1;7Hb{}", FRAME_END);
+        assert_eq!(framebuffer.get_string().unwrap(), expected);
+    }
+    
+    #[test]
+    fn forced_repaint() {
+        let mut framebuffer = presented();
+        framebuffer.force_repaint();
+        let expected = format!("\x13\x1b[0m\x1b[2J\x1b[1;1H{}        \x1b[2;1H        {}", BLANK_COLORS, FRAME_END);
+        assert_eq!(framebuffer.get_string().unwrap(), expected);
+        // Back to differences afterwards
+        assert_eq!(framebuffer.get_string().unwrap(), format!("\x13\x1b[0m{}", FRAME_END));
+    }
+    
+    #[test]
+    fn mode_and_size_changes_repaint() {
+        let mut framebuffer = presented();
+        framebuffer.set_color_mode(ColorMode::Ansi256);
+        assert!(framebuffer.get_string().unwrap().starts_with("\x13\x1b[0m\x1b[2J\x1b[1;1H"));
+        
+        framebuffer.resize(4, 2);
+        let expected = format!("\x13\x1b[0m\x1b[2J\x1b[1;1H\x1b[38;5;16m\x1b[48;5;16m    \x1b[2;1H    {}", FRAME_END);
+        assert_eq!(framebuffer.get_string().unwrap(), expected);
+    }
+    
+    #[test]
+    fn resize_keeps_content() {
+        let red = Color{r: 255, g: 0, b: 0};
+        let mut framebuffer = Framebuffer::new(4, 3);
+        framebuffer.clear('.', WHITE, red);
+        framebuffer.set_styled_cell(0, 0, Some('a'), Some(red), Some(WHITE), Some(Attributes::BOLD));
+        framebuffer.set_cell(1, 1, Some('b'), None, None);
+        framebuffer.set_cell(2, 0, Some('\u{754c}'), None, None);
+        let rows = |framebuffer: &Framebuffer| -> Vec<String> {
+            (0..framebuffer.height).map(|y| {
+                (0..framebuffer.width).map(|x| framebuffer.get_cell(x, y).unwrap().grapheme.to_string()).collect()
+            }).collect()
+        };
+        
+        // Shrinking keeps the top left, and drops wide characters cut in half
+        framebuffer.resize(3, 2);
+        assert_eq!(rows(&framebuffer), ["a. ", ".b."]);
+        assert_eq!(*framebuffer.get_cell(0, 0).unwrap(), Cell{grapheme: Grapheme::from('a'), fg: red, bg: WHITE, attributes: Attributes::BOLD});
+        assert_eq!(framebuffer.get_cell(1, 1).unwrap().bg, red);
+        
+        // Growing keeps everything, and new cells are blank
+        framebuffer.resize(5, 3);
+        assert_eq!(rows(&framebuffer), ["a.   ", ".b.  ", "     "]);
+        assert_eq!(*framebuffer.get_cell(0, 0).unwrap(), Cell{grapheme: Grapheme::from('a'), fg: red, bg: WHITE, attributes: Attributes::BOLD});
+        let blank = Cell{grapheme: Grapheme::default(), fg: BLACK, bg: BLACK, attributes: Attributes::NONE};
+        for (x, y) in [(3, 0), (4, 1), (0, 2), (4, 2)] {
+            assert_eq!(*framebuffer.get_cell(x, y).unwrap(), blank);
+        }
+    }
+    
+    /// Composite and resolve the cells of the next frame
+    fn resolve(framebuffer: &mut Framebuffer) -> Vec<PresentedCell> {
+        let (mut composited, mut cells) = (Vec::new(), Vec::new());
+        framebuffer.composite_into(&mut composited);
+        framebuffer.resolve_cells(&composited, &mut cells);
+        cells
+    }
+    
+    #[test]
+    fn shade_dither_glyphs() {
+        // Only black and white
+        let mut palette = Palette{colors: [WHITE; 16]};
+        palette.colors[0] = BLACK;
+        let mut framebuffer = Framebuffer::new(7, 1);
+        framebuffer.set_palette(palette);
+        framebuffer.set_shade_dither(true);
+        
+        let gray = |level: u8| Color{r: level, g: level, b: level};
+        // Shades mix in linear light, so 50% gray is closest to a quarter
+        // white. Ties go to the darker background
+        for (x, level) in [0, 255, 128, 188, 225].iter().enumerate() {
+            framebuffer.set_cell(x, 0, Some(' '), Some(WHITE), Some(gray(*level)));
+        }
+        // Text and attributes are left alone
+        framebuffer.set_cell(5, 0, Some('x'), Some(WHITE), Some(gray(100)));
+        framebuffer.set_styled_cell(6, 0, Some(' '), Some(WHITE), Some(gray(188)), Some(Attributes::REVERSE));
+        
+        let cells = resolve(&mut framebuffer);
+        let shown: Vec<(String, ConsoleColor, ConsoleColor)> = cells.iter().map(|cell| (cell.grapheme.to_string(), cell.fg, cell.bg)).collect();
+        let cell = |grapheme: &str, fg, bg| (grapheme.to_string(), ConsoleColor::Csi(fg), ConsoleColor::Csi(bg));
+        assert_eq!(shown, [
+            cell(" ", 1, 0),
+            cell(" ", 1, 1),
+            cell("\u{2591}", 1, 0),
+            cell("\u{2592}", 1, 0),
+            cell("\u{2593}", 1, 0),
+            cell("x", 1, 0),
+            cell(" ", 1, 1),
+        ]);
+    }
+    
+    #[test]
+    fn console_attributes() {
+        let sgr = |from: Attributes, to: Attributes| {
+            let (mut buf, mut last) = (Vec::new(), from);
+            Framebuffer::console_attributes(&mut buf, to, &mut last);
+            assert_eq!(last, to);
+            String::from_utf8(buf).unwrap()
+        };
+        let (bold, dim, underline) = (Attributes::BOLD, Attributes::DIM, Attributes::UNDERLINE);
+        
+        assert_eq!(sgr(bold, bold), "");
+        assert_eq!(sgr(Attributes::NONE, bold), "\x1b[1m");
+        assert_eq!(sgr(bold, bold | dim), "\x1b[2m");
+        // Bold and dim are both turned off by 22, so the one that stays on is
+        // turned on again
+        assert_eq!(sgr(bold, dim), "\x1b[22;2m");
+        assert_eq!(sgr(bold | dim, dim), "\x1b[22;2m");
+        assert_eq!(sgr(bold | dim, bold), "\x1b[22;1m");
+        assert_eq!(sgr(bold | dim, Attributes::NONE), "\x1b[22m");
+        assert_eq!(sgr(bold | underline, Attributes::NONE), "\x1b[22;24m");
+        assert_eq!(sgr(bold | underline, underline | Attributes::ITALIC), "\x1b[22;3m");
+        assert_eq!(sgr(dim | Attributes::REVERSE, dim | Attributes::STRIKETHROUGH), "\x1b[27;9m");
+    }
+    
+    #[test]
+    fn composite_layer() {
+        let gray = Color{r: 128, g: 128, b: 128};
+        let red = Color{r: 255, g: 0, b: 0};
+        let mut framebuffer = Framebuffer::new(6, 1);
+        framebuffer.clear('.', WHITE, gray);
+        framebuffer.set_cell(4, 0, Some('\u{754c}'), None, None);
+        
+        let layer = framebuffer.add_layer("overlay");
+        layer.set_cell(0, 0, None, None, Some(Rgba::new(red, 255)));
+        // New characters blend their foreground over the background below
+        layer.set_cell(1, 0, Some('x'), Some(Rgba::new(WHITE, 0)), None);
+        layer.set_cell(2, 0, None, Some(Rgba::new(BLACK, 255)), None);
+        layer.set_attributes(3, 0, Attributes::BOLD);
+        // Drawing over the right half of a wide character breaks it
+        layer.set_cell(5, 0, Some('y'), None, None);
+        
+        let cells = framebuffer.composite();
+        let shown: Vec<(String, Color, Color, Attributes)> = cells.iter().map(|cell| (cell.grapheme.to_string(), cell.fg, cell.bg, cell.attributes)).collect();
+        let none = Attributes::NONE;
+        assert_eq!(shown, [
+            (".".to_string(), WHITE, red, none),
+            ("x".to_string(), gray, gray, none),
+            (".".to_string(), BLACK, gray, none),
+            (".".to_string(), WHITE, gray, Attributes::BOLD),
+            (" ".to_string(), WHITE, gray, none),
+            ("y".to_string(), WHITE, gray, none),
+        ]);
+        // The framebuffer's own cells are untouched
+        assert_eq!(framebuffer.get_cell(0, 0).unwrap().bg, gray);
+        
+        // Hidden and fully transparent layers change nothing
+        let layer = framebuffer.layer_mut("overlay").unwrap();
+        layer.visible = false;
+        let below: Vec<Cell> = framebuffer.cells.clone();
+        assert_eq!(framebuffer.composite(), below);
+        let layer = framebuffer.layer_mut("overlay").unwrap();
+        layer.visible = true;
+        layer.opacity = 0.0;
+        assert_eq!(framebuffer.composite(), below);
+    }
+    
+    #[test]
+    fn composite_blend_mode_and_opacity() {
+        let mut framebuffer = Framebuffer::new(1, 1);
+        framebuffer.clear(' ', WHITE, Color{r: 100, g: 150, b: 200});
+        let layer = framebuffer.add_layer("tint");
+        layer.blend_mode = BlendMode::Multiply;
+        layer.opacity = 0.5;
+        layer.set_cell(0, 0, None, None, Some(Rgba{r: 200, g: 100, b: 50, a: 255}));
+        // Halfway between the bottom color and the multiplied color
+        assert_eq!(framebuffer.composite()[0].bg, Color{r: 89, g: 104, b: 120});
+    }
+    
+    #[test]
+    fn composite_layers_of_other_sizes() {
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.clear('.', WHITE, BLACK);
+        let mut small = Layer::new("small", 2, 1);
+        small.fill(LayerCell{character: Some('s'), ..LayerCell::default()});
+        let mut large = Layer::new("large", 5, 5);
+        large.set_cell(2, 1, Some('l'), None, None);
+        large.set_cell(4, 4, Some('!'), None, None);
+        framebuffer.layers_mut().push(small);
+        framebuffer.layers_mut().push(large);
+        
+        // Only the part of each layer over the framebuffer is drawn
+        let text: String = framebuffer.composite().iter().map(|cell| cell.grapheme.to_string()).collect();
+        assert_eq!(text, "ss...l");
+    }
+}
@@ -37,18 +37,18 @@ impl Point {
         
         if a >= 1.5 * PI {
             let t = a - 1.5 * PI;
-            return Point{x: -length * t.cos(), y: length * t.sin()};
+            Point{x: -length * t.cos(), y: length * t.sin()}
         }
         else if a >= PI {
             let t = a - PI;
-            return Point{x: -length * t.sin(), y: -length * t.cos()};
+            Point{x: -length * t.sin(), y: -length * t.cos()}
         }
         else if a >= 0.5 * PI {
             let t = a - 0.5 * PI;
-            return Point{x: length * t.cos(), y: -length * t.sin()};
+            Point{x: length * t.cos(), y: -length * t.sin()}
         }
         else {
-            return Point{x: length * a.sin(), y: length * a.cos()};
+            Point{x: length * a.sin(), y: length * a.cos()}
         }
     }
     
@@ -85,7 +85,7 @@ pub struct Line {
 
 impl Line {
     pub fn from_angle(start: Point, length: f64, angle: f64) -> Line {
-        Line{start: start, end: start + Point::from_normal(angle, length)}
+        Line{start, end: start + Point::from_normal(angle, length)}
     }
     
     pub fn intersection(&self, other: Line) -> Option<Point> {
@@ -99,14 +99,13 @@ impl Line {
         }
         
         let start_dists = other.start - self.start;
-        let start_cross = start_dists.cross(this_gradient); // TODO wrong formula?
         let this_start_dist = start_dists.cross(other_gradient) / cross_gradient;
         let other_start_dist = start_dists.cross(this_gradient) / cross_gradient;
-        if this_start_dist >= 0.0 && this_start_dist <= 1.0 && other_start_dist >= 0.0 && other_start_dist <= 1.0 {
+        if (0.0..=1.0).contains(&this_start_dist) && (0.0..=1.0).contains(&other_start_dist) {
             return Some(self.start + this_gradient.scale(this_start_dist));
         }
         
-        return None;
+        None
     }
 
     pub fn length(&self) -> f64 {
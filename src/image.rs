@@ -0,0 +1,201 @@
+use crate::color::Color;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// An RGB image, such as a framebuffer exported with `Framebuffer::to_image`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels, row by row from the top left
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    /// Create an image filled with a color
+    pub fn new(width: usize, height: usize, color: Color) -> Image {
+        Image{
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+    
+    /// Decode an image file. PPM and PGM (binary or plain) are always
+    /// supported, PNG needs the `png` feature
+    pub fn parse(data: &[u8]) -> Result<Image, ImageError> {
+        if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+            #[cfg(feature = "png")]
+            return crate::png::decode(data);
+            #[cfg(not(feature = "png"))]
+            return Err(ImageError::Unsupported("PNG images need the `png` feature".to_string()));
+        }
+        
+        match data.get(..2) {
+            Some(b"P2") | Some(b"P3") | Some(b"P5") | Some(b"P6") => Image::parse_pnm(data),
+            _ => Err(ImageError::Unsupported("unknown image format, expected PPM, PGM or PNG".to_string())),
+        }
+    }
+    
+    /// Load an image file. See `parse` for the supported formats
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Image, ImageError> {
+        Image::parse(&fs::read(path)?)
+    }
+    
+    /// Decode a PPM (P3, P6) or PGM (P2, P5) image. Samples are scaled from
+    /// the image's maximum value to 0-255
+    fn parse_pnm(data: &[u8]) -> Result<Image, ImageError> {
+        let error = |message: &str| ImageError::Parse(message.to_string());
+        let (plain, channels) = match &data[..2] {
+            b"P2" => (true, 1),
+            b"P3" => (true, 3),
+            b"P5" => (false, 1),
+            _ => (false, 3),
+        };
+        
+        // Header fields are separated by whitespace, and `#` starts a
+        // comment that runs to the end of the line
+        let mut pos = 2;
+        let mut header = [0usize; 3];
+        for field in header.iter_mut() {
+            loop {
+                match data.get(pos) {
+                    Some(b'#') => while data.get(pos).is_some_and(|&byte| byte != b'\n') { pos += 1 },
+                    Some(byte) if byte.is_ascii_whitespace() => pos += 1,
+                    _ => break,
+                }
+            }
+            let start = pos;
+            while data.get(pos).is_some_and(u8::is_ascii_digit) {
+                pos += 1;
+            }
+            *field = std::str::from_utf8(&data[start..pos]).ok().and_then(|digits| digits.parse().ok()).ok_or_else(|| error("invalid header"))?;
+        }
+        let [width, height, max_value] = header;
+        if max_value == 0 || max_value > 0xFFFF {
+            return Err(error("maximum value must be between 1 and 65535"));
+        }
+        
+        let count = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels)).ok_or_else(|| error("image is too large"))?;
+        let samples: Vec<usize> = if plain {
+            let text = std::str::from_utf8(&data[pos..]).map_err(|_| error("invalid sample"))?;
+            text.lines()
+                .map(|line| line.split('#').next().unwrap_or(""))
+                .flat_map(str::split_whitespace)
+                .take(count)
+                .map(|sample| sample.parse().map_err(|_| error("invalid sample")))
+                .collect::<Result<_, _>>()?
+        }
+        else {
+            // A single whitespace byte separates the header from the samples,
+            // which take two big endian bytes if the maximum is above 255
+            let bytes = if max_value > 255 { 2 } else { 1 };
+            let raster = data.get(pos + 1..).unwrap_or(&[]);
+            let size = count.checked_mul(bytes).ok_or_else(|| error("image is too large"))?;
+            if raster.len() < size {
+                return Err(error("image data is truncated"));
+            }
+            raster.chunks_exact(bytes).take(count).map(|sample| {
+                sample.iter().fold(0, |value, &byte| value << 8 | byte as usize)
+            }).collect()
+        };
+        if samples.len() < count {
+            return Err(error("image data is truncated"));
+        }
+        
+        let scale = |sample: usize| (sample.min(max_value) * 255 + max_value / 2) / max_value;
+        let pixels = samples.chunks_exact(channels).map(|pixel| {
+            let (r, g, b) = if channels == 1 { (pixel[0], pixel[0], pixel[0]) } else { (pixel[0], pixel[1], pixel[2]) };
+            Color{r: scale(r) as u8, g: scale(g) as u8, b: scale(b) as u8}
+        }).collect();
+        
+        Ok(Image{
+            width,
+            height,
+            pixels,
+        })
+    }
+    
+    /// Get a pixel, or `None` if out of bounds
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Color> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        
+        Some(self.pixels[y * self.width + x])
+    }
+    
+    /// Set a pixel. Does nothing if out of bounds
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+    
+    /// Fill a rectangle, clipped to the image
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        for pixel_y in y..(y + height).min(self.height) {
+            for pixel_x in x..(x + width).min(self.width) {
+                self.pixels[pixel_y * self.width + pixel_x] = color;
+            }
+        }
+    }
+    
+    /// Get the pixels as packed RGB bytes
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|color| [color.r, color.g, color.b]).collect()
+    }
+    
+    /// Write the image as a binary PPM (P6) file
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.to_rgb_bytes())?;
+        out.flush()
+    }
+    
+    /// Write the image as a PNG file. The image data is stored without
+    /// compression, which keeps the encoder small
+    #[cfg(feature = "png")]
+    pub fn write_png(&self, out: &mut impl Write) -> io::Result<()> {
+        crate::png::encode(out, self.width, self.height, &self.to_rgb_bytes())?;
+        out.flush()
+    }
+}
+
+/// Error returned when loading an image
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The file is in a format that can't be decoded
+    Unsupported(String),
+    /// Malformed image
+    Parse(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "could not read image: {}", error),
+            ImageError::Unsupported(message) => write!(f, "{}", message),
+            ImageError::Parse(message) => write!(f, "invalid image: {}", message),
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImageError::Io(error) => Some(error),
+            ImageError::Unsupported(_) | ImageError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(error: io::Error) -> ImageError {
+        ImageError::Io(error)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::attributes::Attributes;
+use crate::color::{BlendMode, Rgba};
+
+/// A layer cell. Channels set to `None` let the layers below show through
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayerCell {
+    pub character: Option<char>,
+    pub fg: Option<Rgba>,
+    pub bg: Option<Rgba>,
+    pub attributes: Option<Attributes>,
+}
+
+/// A named layer drawn over the framebuffer's cells when presenting. Layers
+/// are composited bottom to top, each with its own blend mode and opacity
+///
+/// A character replaces the character below it. If the cell also has a
+/// foreground color, it is blended over the background below, as the old
+/// character is gone
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub name: String,
+    width: usize,
+    height: usize,
+    pub blend_mode: BlendMode,
+    /// Multiplies the alpha of every cell, from 0 to 1
+    pub opacity: f64,
+    pub visible: bool,
+    cells: Vec<LayerCell>,
+}
+
+impl Layer {
+    /// Create a new, fully transparent layer
+    pub fn new(name: &str, width: usize, height: usize) -> Layer {
+        Layer{
+            name: name.to_string(),
+            width,
+            height,
+            blend_mode: BlendMode::Normal,
+            opacity: 1.0,
+            visible: true,
+            cells: vec![LayerCell::default(); width * height],
+        }
+    }
+    
+    /// Get the width of the layer in cells
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    
+    /// Get the height of the layer in cells
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    
+    /// Make every cell transparent
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = LayerCell::default();
+        }
+    }
+    
+    /// Fill every cell with a cell
+    pub fn fill(&mut self, cell: LayerCell) {
+        for layer_cell in self.cells.iter_mut() {
+            *layer_cell = cell;
+        }
+    }
+    
+    /// Set a cell's character, foreground and or background colors
+    pub fn set_cell(&mut self, x: usize, y: usize, character: Option<char>, fg: Option<Rgba>, bg: Option<Rgba>) {
+        // Abort if out of bounds
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        
+        let cell = &mut self.cells[y * self.width + x];
+        if character.is_some() {
+            cell.character = character;
+        }
+        if fg.is_some() {
+            cell.fg = fg;
+        }
+        if bg.is_some() {
+            cell.bg = bg;
+        }
+    }
+    
+    /// Set a cell's attributes. They replace the attributes below
+    pub fn set_attributes(&mut self, x: usize, y: usize, attributes: Attributes) {
+        // Abort if out of bounds
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        
+        self.cells[y * self.width + x].attributes = Some(attributes);
+    }
+    
+    /// Get a cell, or `None` if out of bounds
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<&LayerCell> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        
+        self.cells.get(y * self.width + x)
+    }
+    
+    /// Resize the layer, keeping the cells that are still in bounds. New cells
+    /// are transparent
+    pub(crate) fn resize(&mut self, width: usize, height: usize) {
+        let mut cells = vec![LayerCell::default(); width * height];
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                cells[y * width + x] = self.cells[y * self.width + x];
+            }
+        }
+        
+        self.cells = cells;
+        self.width = width;
+        self.height = height;
+    }
+}
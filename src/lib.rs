@@ -0,0 +1,27 @@
+//! Terminal framebuffer with a DOOM-style ray casting renderer on top.
+//!
+//! The framebuffer, colors, geometry and textures are always available. The
+//! ray caster ([`world`]) lives behind the `raycaster` feature, which is
+//! enabled by default.
+
+pub mod ansi;
+pub mod asciicast;
+pub mod attributes;
+pub mod canvas;
+pub mod color;
+pub mod csi_color;
+pub mod dither;
+pub mod draw;
+pub mod export;
+pub mod framebuffer;
+pub mod geometry;
+pub mod image;
+pub mod layer;
+pub mod palette;
+#[cfg(feature = "png")]
+mod png;
+pub mod terminal;
+pub mod texture;
+pub mod unicode;
+#[cfg(feature = "raycaster")]
+pub mod world;
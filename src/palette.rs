@@ -0,0 +1,280 @@
+use crate::color::Color;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Names of the palette entries, in order. Bright variants are also accepted
+/// with a `bright_` prefix or as `color8` to `color15`
+const COLOR_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// Build a palette from 0xRRGGBB values
+const fn palette_from_hex(hex: [u32; 16]) -> Palette {
+    let mut colors = [Color{r: 0, g: 0, b: 0}; 16];
+    let mut i = 0;
+    while i < 16 {
+        colors[i] = Color{r: (hex[i] >> 16) as u8, g: (hex[i] >> 8) as u8, b: hex[i] as u8};
+        i += 1;
+    }
+    Palette{colors}
+}
+
+/// The 16 colors a terminal shows for the basic SGR color codes. In order:
+/// black, red, green, yellow, blue, magenta, cyan and white, then their
+/// bright variants
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [Color; 16],
+}
+
+impl Palette {
+    /// Windows Terminal "Campbell", also the Windows 10 console default
+    pub const CAMPBELL: Palette = palette_from_hex([
+        0x0c0c0c, 0xc50f1f, 0x13a10e, 0xc19c00, 0x0037da, 0x881798, 0x3a96dd, 0xcccccc,
+        0x767676, 0xe74856, 0x16c60c, 0xf9f1a5, 0x3b78ff, 0xb4009e, 0x61d6d6, 0xf2f2f2,
+    ]);
+    
+    /// xterm defaults
+    pub const XTERM: Palette = palette_from_hex([
+        0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5,
+        0x7f7f7f, 0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+    ]);
+    
+    /// IBM VGA text mode
+    pub const VGA: Palette = palette_from_hex([
+        0x000000, 0xaa0000, 0x00aa00, 0xaa5500, 0x0000aa, 0xaa00aa, 0x00aaaa, 0xaaaaaa,
+        0x555555, 0xff5555, 0x55ff55, 0xffff55, 0x5555ff, 0xff55ff, 0x55ffff, 0xffffff,
+    ]);
+    
+    /// Tango, the GNOME Terminal default
+    pub const TANGO: Palette = palette_from_hex([
+        0x2e3436, 0xcc0000, 0x4e9a06, 0xc4a000, 0x3465a4, 0x75507b, 0x06989a, 0xd3d7cf,
+        0x555753, 0xef2929, 0x8ae234, 0xfce94f, 0x729fcf, 0xad7fa8, 0x34e2e2, 0xeeeeec,
+    ]);
+    
+    /// Solarized. Dark and light variants share the same 16 colors
+    pub const SOLARIZED: Palette = palette_from_hex([
+        0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5,
+        0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+    ]);
+    
+    /// Get a built-in palette by name (`campbell`, `windows`, `xterm`, `vga`,
+    /// `tango` or `solarized`)
+    pub fn preset(name: &str) -> Option<Palette> {
+        match name.to_lowercase().as_str() {
+            "campbell" | "windows" => Some(Palette::CAMPBELL),
+            "xterm" => Some(Palette::XTERM),
+            "vga" => Some(Palette::VGA),
+            "tango" => Some(Palette::TANGO),
+            "solarized" => Some(Palette::SOLARIZED),
+            _ => None,
+        }
+    }
+    
+    /// Get the SGR foreground code of a palette entry
+    pub fn fg_code(index: usize) -> u8 {
+        if index < 8 { 30 + index as u8 } else { 90 + (index - 8) as u8 }
+    }
+    
+    /// Get the SGR background code of a palette entry
+    pub fn bg_code(index: usize) -> u8 {
+        Palette::fg_code(index) + 10
+    }
+    
+    /// Parse a terminal theme. Both `key = #rrggbb` lines and
+    /// Xresources-style `*color0: #rrggbb` lines are accepted. Keys are
+    /// `color0` to `color15` or color names (`red`, `bright_red`, ...), and
+    /// may be prefixed by a resource path (`URxvt.`, `*.`). Colors are
+    /// `#rrggbb`, `#rgb` or `rgb:rr/gg/bb`. Unknown keys, like `background`,
+    /// are ignored and missing entries are taken from the default palette.
+    /// Lines starting with `#`, `!` or `;` are comments
+    pub fn parse(text: &str) -> Result<Palette, PaletteError> {
+        let mut palette = Palette::default();
+        
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('!') || line.starts_with(';') {
+                continue;
+            }
+            
+            let error = |message: String| PaletteError::Parse{line: line_index + 1, message};
+            let separator = line.find(['=', ':']).ok_or_else(|| error(format!("expected `key = color` or `key: color`, got `{}`", line)))?;
+            let (key, value) = (line[..separator].trim(), line[separator + 1..].trim());
+            
+            // Strip resource paths like `*`, `*.` or `URxvt.`
+            let key = key.rsplit(['.', '*']).next().unwrap_or(key);
+            let index = match Palette::key_index(key) {
+                Some(index) => index,
+                None => continue,
+            };
+            
+            palette.colors[index] = Palette::parse_color(value).ok_or_else(|| error(format!("invalid color `{}` for `{}`", value, key)))?;
+        }
+        
+        Ok(palette)
+    }
+    
+    /// Load a terminal theme file. See `parse` for the format
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Palette, PaletteError> {
+        Palette::parse(&fs::read_to_string(path)?)
+    }
+    
+    /// Get the palette entry a theme key refers to
+    fn key_index(key: &str) -> Option<usize> {
+        let key = key.to_lowercase();
+        if let Some(number) = key.strip_prefix("color") {
+            return number.parse::<usize>().ok().filter(|&index| index < 16);
+        }
+        
+        let (name, offset) = match key.strip_prefix("bright_").or_else(|| key.strip_prefix("bright")) {
+            Some(name) => (name, 8),
+            None => (key.as_str(), 0),
+        };
+        COLOR_NAMES.iter().position(|&color_name| color_name == name).map(|index| index + offset)
+    }
+    
+    /// Parse a `#rrggbb`, `#rgb` or `rgb:rr/gg/bb` color
+    pub(crate) fn parse_color(value: &str) -> Option<Color> {
+        let channels: Vec<u8> = if let Some(hex) = value.strip_prefix('#') {
+            match hex.len() {
+                6 => (0..3).map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()).collect::<Option<_>>()?,
+                3 => (0..3).map(|i| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok().map(|c| c * 17)).collect::<Option<_>>()?,
+                _ => return None,
+            }
+        }
+        else if let Some(rgb) = value.strip_prefix("rgb:") {
+            // X11 allows 1 to 4 hex digits per channel, scaled to the full range
+            rgb.split('/').map(|channel| {
+                let scale = match channel.len() {
+                    1 => 0xf,
+                    2 => 0xff,
+                    3 => 0xfff,
+                    4 => 0xffff,
+                    _ => return None,
+                };
+                u32::from_str_radix(channel, 16).ok().map(|c| (c * 255 / scale) as u8)
+            }).collect::<Option<_>>()?
+        }
+        else {
+            return None;
+        };
+        
+        if channels.len() != 3 {
+            return None;
+        }
+        Some(Color{r: channels[0], g: channels[1], b: channels[2]})
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::CAMPBELL
+    }
+}
+
+/// Error returned when loading a palette
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    /// Malformed theme. `line` starts at 1
+    Parse{line: usize, message: String},
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(error) => write!(f, "could not read palette: {}", error),
+            PaletteError::Parse{line, message} => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for PaletteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PaletteError::Io(error) => Some(error),
+            PaletteError::Parse{..} => None,
+        }
+    }
+}
+
+impl From<io::Error> for PaletteError {
+    fn from(error: io::Error) -> PaletteError {
+        PaletteError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color{r, g, b}
+    }
+    
+    fn error_line(text: &str) -> usize {
+        match Palette::parse(text) {
+            Err(PaletteError::Parse{line, ..}) => line,
+            result => panic!("expected a parse error, got {:?}", result),
+        }
+    }
+    
+    #[test]
+    fn key_value_themes() {
+        let palette = Palette::parse("color0 = #102030\nred=#ff0000\n  bright_blue =  #0000FF  \nBrightWhite = #fff\n").unwrap();
+        assert_eq!(palette.colors[0], rgb(0x10, 0x20, 0x30));
+        assert_eq!(palette.colors[1], rgb(255, 0, 0));
+        assert_eq!(palette.colors[12], rgb(0, 0, 255));
+        assert_eq!(palette.colors[15], rgb(255, 255, 255));
+        // Entries that aren't set keep their defaults
+        assert_eq!(palette.colors[2], Palette::CAMPBELL.colors[2]);
+    }
+    
+    #[test]
+    fn xresources_themes() {
+        let text = "! comment\n*color0: #000000\n*.color8: rgb:80/80/80\nURxvt.color9: rgb:f/0/0\nXTerm*color10: rgb:0000/ffff/4000\n";
+        let palette = Palette::parse(text).unwrap();
+        assert_eq!(palette.colors[0], rgb(0, 0, 0));
+        assert_eq!(palette.colors[8], rgb(0x80, 0x80, 0x80));
+        assert_eq!(palette.colors[9], rgb(255, 0, 0));
+        assert_eq!(palette.colors[10], rgb(0, 255, 0x3f));
+    }
+    
+    #[test]
+    fn colors() {
+        assert_eq!(Palette::parse_color("#a1B2c3"), Some(rgb(0xa1, 0xb2, 0xc3)));
+        assert_eq!(Palette::parse_color("#1af"), Some(rgb(0x11, 0xaa, 0xff)));
+        assert_eq!(Palette::parse_color("rgb:12/ab/ff"), Some(rgb(0x12, 0xab, 0xff)));
+        assert_eq!(Palette::parse_color("rgb:fff/800/000"), Some(rgb(255, 127, 0)));
+        for invalid in ["", "#12345", "#12345g", "rgb:12/34", "rgb:12/34/56/78", "rgb:12345/0/0", "red", "12,34,56"] {
+            assert_eq!(Palette::parse_color(invalid), None, "{:?}", invalid);
+        }
+    }
+    
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let palette = Palette::parse("background = #123456\ncursorColor: #abcdef\ncolor16 = #ffffff\nforeground = not a color\n").unwrap();
+        assert_eq!(palette, Palette::default());
+    }
+    
+    #[test]
+    fn errors_have_line_numbers() {
+        assert_eq!(error_line("color1 = #ff0000\n\ncolor2 = #zzzzzz\n"), 3);
+        assert_eq!(error_line("# comment\ncolor1 #ff0000\n"), 2);
+        assert_eq!(error_line("; comment\n! comment\n*color3: rgb:1/2\n"), 3);
+        
+        let message = Palette::parse("red = blue").unwrap_err().to_string();
+        assert_eq!(message, "line 1: invalid color `blue` for `red`");
+    }
+    
+    #[test]
+    fn sgr_codes() {
+        assert_eq!((Palette::fg_code(0), Palette::bg_code(0)), (30, 40));
+        assert_eq!((Palette::fg_code(7), Palette::bg_code(7)), (37, 47));
+        // Bright colors use the aixterm codes
+        assert_eq!((Palette::fg_code(8), Palette::bg_code(8)), (90, 100));
+        assert_eq!((Palette::fg_code(9), Palette::bg_code(9)), (91, 101));
+        assert_eq!((Palette::fg_code(15), Palette::bg_code(15)), (97, 107));
+    }
+}
@@ -0,0 +1,527 @@
+//! Minimal PNG encoder: 8-bit RGB, no filtering, and zlib streams made of
+//! stored (uncompressed) deflate blocks. The decoder reads any
+//! non-interlaced PNG, dropping the alpha channel
+
+use crate::color::Color;
+use crate::image::{Image, ImageError};
+use std::io::{self, Write};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Largest amount of data in a stored deflate block
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+/// CRC-32 as used by PNG chunks
+fn crc32(data: &[&[u8]]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    
+    let mut crc = 0xFFFFFFFFu32;
+    for &byte in data.iter().flat_map(|part| part.iter()) {
+        crc = table[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xFFFFFFFF
+}
+
+/// Adler-32 as used by zlib streams
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Wrap data in a zlib stream of stored deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    let mut out = Vec::with_capacity(data.len() + blocks * 5 + 6);
+    // Deflate, 32K window, no preset dictionary, fastest compression
+    out.extend_from_slice(&[0x78, 0x01]);
+    
+    let mut chunks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if chunks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(chunk) = chunks.next() {
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// Write a PNG chunk
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+/// Encode packed 8-bit RGB pixels as a PNG file
+pub(crate) fn encode(out: &mut impl Write, width: usize, height: usize, rgb: &[u8]) -> io::Result<()> {
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, adaptive filtering, no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    
+    // Every scanline starts with its filter type, 0 for none
+    let mut scanlines = Vec::with_capacity(height * (width * 3 + 1));
+    let stride = width * 3;
+    for y in 0..height {
+        scanlines.push(0);
+        scanlines.extend_from_slice(&rgb[y * stride..(y + 1) * stride]);
+    }
+    
+    out.write_all(&SIGNATURE)?;
+    write_chunk(out, b"IHDR", &header)?;
+    write_chunk(out, b"IDAT", &zlib_stored(&scanlines))?;
+    write_chunk(out, b"IEND", &[])
+}
+
+/// Base lengths and extra bits of deflate length codes 257 to 285
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+
+/// Base distances and extra bits of deflate distance codes 0 to 29
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Order in which code length code lengths are stored in dynamic blocks
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(message: &str) -> ImageError {
+    ImageError::Parse(message.to_string())
+}
+
+/// Reads a deflate stream bit by bit, least significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, ImageError> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or_else(|| invalid("compressed data is truncated"))?;
+            value |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+    
+    /// Skip to the next byte boundary
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, stored as the amount of codes of each length and
+/// the symbols sorted by code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        
+        Huffman{counts, symbols}
+    }
+    
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ImageError> {
+        // Codes of each length follow the last code of the previous length
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+/// Decode the literal/length and distance codes of a dynamic block
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ImageError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let dist_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+    
+    let mut lengths = Vec::with_capacity(literal_count + dist_count);
+    while lengths.len() < literal_count + dist_count {
+        let (length, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or_else(|| invalid("repeated code length without a previous one"))?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > literal_count + dist_count {
+        return Err(invalid("code lengths overflow"));
+    }
+    
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+/// Decompress a zlib stream, failing if it holds more than `max_len` bytes
+fn inflate(data: &[u8], max_len: usize) -> Result<Vec<u8>, ImageError> {
+    let too_long = || invalid("image data is larger than the image");
+    if data.len() < 6 || data[0] & 0x0F != 8 || (data[0] as u16 * 256 + data[1] as u16) % 31 != 0 || data[1] & 0x20 != 0 {
+        return Err(invalid("invalid zlib header"));
+    }
+    
+    let mut reader = BitReader{data: &data[2..], pos: 0, bit: 0};
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = reader.data.get(reader.pos..reader.pos + 4).ok_or_else(|| invalid("compressed data is truncated"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if u16::from_le_bytes([header[2], header[3]]) as usize != !len & 0xFFFF {
+                    return Err(invalid("invalid stored block length"));
+                }
+                reader.pos += 4;
+                if out.len() + len > max_len {
+                    return Err(too_long());
+                }
+                out.extend_from_slice(reader.data.get(reader.pos..reader.pos + len).ok_or_else(|| invalid("compressed data is truncated"))?);
+                reader.pos += len;
+            },
+            block_type @ (1 | 2) => {
+                let (literals, distances) = if block_type == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                }
+                else {
+                    dynamic_codes(&mut reader)?
+                };
+                
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        if out.len() == max_len {
+                            return Err(too_long());
+                        }
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    
+                    let symbol = symbol - 257;
+                    if symbol >= LENGTH_BASE.len() {
+                        return Err(invalid("invalid length code"));
+                    }
+                    let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+                    let dist_symbol = distances.decode(&mut reader)? as usize;
+                    if dist_symbol >= DIST_BASE.len() {
+                        return Err(invalid("invalid distance code"));
+                    }
+                    let dist = DIST_BASE[dist_symbol] as usize + reader.bits(DIST_EXTRA[dist_symbol] as u32)? as usize;
+                    if dist > out.len() {
+                        return Err(invalid("distance reaches before the start of the data"));
+                    }
+                    if out.len() + length > max_len {
+                        return Err(too_long());
+                    }
+                    // Copies may overlap the bytes they produce
+                    let start = out.len() - dist;
+                    for i in 0..length {
+                        out.push(out[start + i]);
+                    }
+                }
+            },
+            _ => return Err(invalid("invalid deflate block type")),
+        }
+        
+        if last {
+            break;
+        }
+    }
+    
+    reader.align();
+    let checksum = reader.data.get(reader.pos..reader.pos + 4).ok_or_else(|| invalid("missing zlib checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&out) {
+        return Err(invalid("zlib checksum mismatch"));
+    }
+    Ok(out)
+}
+
+/// Undo the per-scanline filters, in place. `bpp` is the amount of bytes per
+/// pixel, rounded up to 1
+fn unfilter(data: &mut [u8], height: usize, stride: usize, bpp: usize) -> Result<(), ImageError> {
+    for y in 0..height {
+        let (previous, rest) = data.split_at_mut(y * (stride + 1));
+        let previous = if y == 0 { None } else { Some(&previous[previous.len() - stride..]) };
+        let (filter, line) = rest[..stride + 1].split_first_mut().unwrap();
+        for x in 0..stride {
+            let left = if x >= bpp { line[x - bpp] } else { 0 };
+            let up = previous.map_or(0, |previous| previous[x]);
+            let up_left = if x >= bpp { previous.map_or(0, |previous| previous[x - bpp]) } else { 0 };
+            let predicted = match *filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => {
+                    let p = left as i16 + up as i16 - up_left as i16;
+                    let (pa, pb, pc) = ((p - left as i16).abs(), (p - up as i16).abs(), (p - up_left as i16).abs());
+                    if pa <= pb && pa <= pc { left } else if pb <= pc { up } else { up_left }
+                },
+                _ => return Err(invalid("invalid scanline filter")),
+            };
+            line[x] = line[x].wrapping_add(predicted);
+        }
+    }
+    Ok(())
+}
+
+/// Decode a PNG file
+pub(crate) fn decode(data: &[u8]) -> Result<Image, ImageError> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("missing PNG signature"));
+    }
+    
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut pos = SIGNATURE.len();
+    loop {
+        let length = data.get(pos..pos + 4).ok_or_else(|| invalid("missing IEND chunk"))?;
+        let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+        let chunk = data.get(pos + 4..pos + 12 + length).ok_or_else(|| invalid("chunk is truncated"))?;
+        let (kind, rest) = chunk.split_at(4);
+        let (body, crc) = rest.split_at(length);
+        if crc32(&[kind, body]).to_be_bytes() != crc {
+            return Err(invalid("chunk checksum mismatch"));
+        }
+        pos += 12 + length;
+        
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(body.to_vec()),
+            b"PLTE" => palette = body.chunks_exact(3).map(|rgb| Color{r: rgb[0], g: rgb[1], b: rgb[2]}).collect(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {},
+        }
+    }
+    
+    let header = header.ok_or_else(|| invalid("missing IHDR chunk"))?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (depth, color_type) = (header[8] as usize, header[9]);
+    if header[12] != 0 {
+        return Err(ImageError::Unsupported("interlaced PNG images are not supported".to_string()));
+    }
+    let channels = match (color_type, depth) {
+        (0, 1 | 2 | 4 | 8 | 16) => 1,
+        (2, 8 | 16) => 3,
+        (3, 1 | 2 | 4 | 8) => 1,
+        (4, 8 | 16) => 2,
+        (6, 8 | 16) => 4,
+        _ => return Err(invalid("invalid color type and bit depth")),
+    };
+    
+    if width == 0 || height == 0 {
+        return Err(invalid("image has no pixels"));
+    }
+    
+    // The size comes from the file, so it can't be trusted not to overflow
+    let too_large = || invalid("image is too large");
+    let bits_per_pixel = channels * depth;
+    let stride = width.checked_mul(bits_per_pixel).ok_or_else(too_large)?.div_ceil(8);
+    let data_len = (stride + 1).checked_mul(height).ok_or_else(too_large)?;
+    let pixel_count = width.checked_mul(height).ok_or_else(too_large)?;
+    let mut scanlines = inflate(&compressed, data_len)?;
+    if scanlines.len() < data_len {
+        return Err(invalid("image data is truncated"));
+    }
+    unfilter(&mut scanlines, height, stride, bits_per_pixel.div_ceil(8))?;
+    
+    // Read samples scaled to 8 bits, keeping the high byte of 16-bit ones
+    let sample = |line: &[u8], index: usize| -> usize {
+        match depth {
+            16 => line[index * 2] as usize,
+            8 => line[index] as usize,
+            _ => {
+                let bit = index * depth;
+                (line[bit / 8] >> (8 - depth - bit % 8)) as usize & ((1 << depth) - 1)
+            },
+        }
+    };
+    let max_sample = if depth >= 8 { 255 } else { (1 << depth) - 1 };
+    
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for y in 0..height {
+        let line = &scanlines[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for x in 0..width {
+            let first = x * channels;
+            let color = match color_type {
+                3 => *palette.get(sample(line, first)).ok_or_else(|| invalid("palette index out of range"))?,
+                0 | 4 => {
+                    let gray = (sample(line, first) * 255 / max_sample) as u8;
+                    Color{r: gray, g: gray, b: gray}
+                },
+                _ => Color{r: sample(line, first) as u8, g: sample(line, first + 1) as u8, b: sample(line, first + 2) as u8},
+            };
+            pixels.push(color);
+        }
+    }
+    
+    Ok(Image{
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Build a PNG file from an IHDR body and an IDAT body
+    fn png(header: &[u8], idat: &[u8]) -> Vec<u8> {
+        let mut out = SIGNATURE.to_vec();
+        write_chunk(&mut out, b"IHDR", header).unwrap();
+        write_chunk(&mut out, b"IDAT", idat).unwrap();
+        write_chunk(&mut out, b"IEND", &[]).unwrap();
+        out
+    }
+    
+    /// IHDR body for a non-interlaced image
+    fn header(width: u32, height: u32, depth: u8, color_type: u8) -> Vec<u8> {
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+        header
+    }
+    
+    fn encoded() -> Vec<u8> {
+        let rgb: Vec<u8> = (0..4 * 3 * 3).map(|i| i as u8 * 7).collect();
+        let mut out = Vec::new();
+        encode(&mut out, 4, 3, &rgb).unwrap();
+        out
+    }
+    
+    fn parse_error(data: &[u8]) -> String {
+        match decode(data) {
+            Err(ImageError::Parse(message)) => message,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn round_trip() {
+        let image = decode(&encoded()).unwrap();
+        assert_eq!((image.width, image.height), (4, 3));
+        assert_eq!(image.to_rgb_bytes(), (0..4 * 3 * 3).map(|i| i as u8 * 7).collect::<Vec<u8>>());
+    }
+    
+    #[test]
+    fn truncated_files() {
+        let data = encoded();
+        for len in 0..data.len() {
+            assert!(decode(&data[..len]).is_err(), "decoded a file truncated to {} bytes", len);
+        }
+    }
+    
+    #[test]
+    fn bad_checksums() {
+        let mut data = encoded();
+        // Last byte of the IDAT body, before its CRC and the IEND chunk
+        let idat_end = data.len() - 12 - 4;
+        data[idat_end - 1] ^= 0xFF;
+        assert_eq!(parse_error(&data), "chunk checksum mismatch");
+        
+        // Valid chunk, but the zlib checksum doesn't match the data
+        let mut idat = zlib_stored(&[0; 4]);
+        let len = idat.len();
+        idat[len - 1] ^= 0xFF;
+        assert_eq!(parse_error(&png(&header(1, 1, 8, 2), &idat)), "zlib checksum mismatch");
+    }
+    
+    #[test]
+    fn corrupt_streams() {
+        let header = header(1, 1, 8, 2);
+        assert_eq!(parse_error(&png(&header, &[0x78, 0x02, 0, 0, 0, 0])), "invalid zlib header");
+        // Block type 3 is reserved
+        assert_eq!(parse_error(&png(&header, &[0x78, 0x01, 0x07, 0, 0, 0, 0])), "invalid deflate block type");
+        // Dynamic block where every code length code is unused
+        assert_eq!(parse_error(&png(&header, &[0x78, 0x01, 0x05, 0, 0, 0, 0, 0])), "invalid Huffman code");
+        // Fixed block starting with a copy, which has nothing to copy from
+        assert_eq!(parse_error(&png(&header, &[0x78, 0x01, 0x03, 0x02, 0, 0, 0, 0])), "distance reaches before the start of the data");
+        // Stored block with a length that doesn't match its complement
+        assert_eq!(parse_error(&png(&header, &[0x78, 0x01, 0x01, 4, 0, 0, 0, 0, 0, 0, 0])), "invalid stored block length");
+        // More data than a 1x1 image holds
+        assert_eq!(parse_error(&png(&header, &zlib_stored(&[0; 64]))), "image data is larger than the image");
+    }
+    
+    #[test]
+    fn oversized_dimensions() {
+        let idat = zlib_stored(&[0; 4]);
+        assert_eq!(parse_error(&png(&header(u32::MAX, u32::MAX, 16, 6), &idat)), "image is too large");
+        assert_eq!(parse_error(&png(&header(0x10000, 0x10000, 8, 2), &idat)), "image data is truncated");
+        assert_eq!(parse_error(&png(&header(0, 1, 8, 2), &idat)), "image has no pixels");
+    }
+}
@@ -0,0 +1,293 @@
+use crate::csi_color::ColorMode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set of glyphs that can be used when rendering
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GlyphSet {
+    /// Unicode block shades (`\u{2591}`, `\u{2592}`, `\u{2593}`)
+    Unicode,
+    /// Pure ASCII fallbacks
+    Ascii,
+}
+
+impl GlyphSet {
+    /// Shade characters, from most to least foreground coverage
+    pub fn shades(&self) -> [char; 4] {
+        match self {
+            GlyphSet::Unicode => ['\u{2593}', '\u{2592}', '\u{2591}', ' '],
+            GlyphSet::Ascii => ['#', '+', '.', ' '],
+        }
+    }
+}
+
+/// What the terminal is able to display. Detect it from the environment with
+/// `Capabilities::detect`, or build one by hand to force a profile
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub color_mode: ColorMode,
+    pub glyphs: GlyphSet,
+}
+
+impl Capabilities {
+    /// Detect terminal capabilities from the process environment
+    pub fn detect() -> Capabilities {
+        Capabilities::from_env(|key| std::env::var(key).ok())
+    }
+    
+    /// Detect terminal capabilities from environment variables returned by a
+    /// lookup function. Looks at `NO_COLOR`, `COLORTERM` and `TERM` for the
+    /// color mode and at `LC_ALL`, `LC_CTYPE` and `LANG` for the glyph set
+    pub fn from_env<F: Fn(&str) -> Option<String>>(env: F) -> Capabilities {
+        Capabilities{
+            color_mode: Capabilities::detect_color_mode(&env),
+            glyphs: Capabilities::detect_glyphs(&env),
+        }
+    }
+    
+    fn detect_color_mode<F: Fn(&str) -> Option<String>>(env: &F) -> ColorMode {
+        // https://no-color.org/: any non-empty value disables colors
+        if env("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return ColorMode::Monochrome;
+        }
+        
+        let colorterm = env("COLORTERM").unwrap_or_default().to_lowercase();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return ColorMode::TrueColor;
+        }
+        
+        let term = env("TERM").unwrap_or_default().to_lowercase();
+        if term == "dumb" {
+            ColorMode::Monochrome
+        }
+        else if term.ends_with("-direct") {
+            ColorMode::TrueColor
+        }
+        else if term.contains("256color") {
+            ColorMode::Ansi256
+        }
+        else {
+            ColorMode::Ansi16
+        }
+    }
+    
+    fn detect_glyphs<F: Fn(&str) -> Option<String>>(env: &F) -> GlyphSet {
+        // The first non-empty locale variable wins, same as setlocale
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+            .filter_map(|key| env(key))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            GlyphSet::Unicode
+        }
+        else {
+            GlyphSet::Ascii
+        }
+    }
+}
+
+/// Set by the SIGWINCH handler, cleared by `resized`
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+/// Terminal size and resize signal for platforms whose ioctl request, signal
+/// numbers and `struct sigaction` layout are declared here
+#[cfg(any(
+    all(target_os = "linux", any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64")),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+))]
+mod sys {
+    use std::os::raw::{c_int, c_ulong, c_ushort};
+    use std::sync::atomic::Ordering;
+    
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(target_os = "linux"))]
+    const TIOCGWINSZ: c_ulong = 0x40087468;
+    #[cfg(target_os = "linux")]
+    const SA_RESTART: c_int = 0x10000000;
+    #[cfg(not(target_os = "linux"))]
+    const SA_RESTART: c_int = 0x0002;
+    const SIGWINCH: c_int = 28;
+    const STDOUT_FILENO: c_int = 1;
+    
+    #[repr(C)]
+    #[derive(Default)]
+    struct Winsize {
+        ws_row: c_ushort,
+        ws_col: c_ushort,
+        ws_xpixel: c_ushort,
+        ws_ypixel: c_ushort,
+    }
+    
+    /// `struct sigaction`. An all zero `sa_mask` is the empty signal set
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct SigAction {
+        sa_handler: extern "C" fn(c_int),
+        sa_mask: [c_ulong; 128 / std::mem::size_of::<c_ulong>()],
+        sa_flags: c_int,
+        sa_restorer: usize,
+    }
+    
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    #[repr(C)]
+    struct SigAction {
+        sa_handler: extern "C" fn(c_int),
+        sa_mask: u32,
+        sa_flags: c_int,
+    }
+    
+    #[cfg(target_os = "freebsd")]
+    #[repr(C)]
+    struct SigAction {
+        sa_handler: extern "C" fn(c_int),
+        sa_flags: c_int,
+        sa_mask: [u32; 4],
+    }
+    
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+        fn sigaction(signum: c_int, act: *const SigAction, old_act: *mut SigAction) -> c_int;
+    }
+    
+    pub fn size() -> Option<(usize, usize)> {
+        let mut winsize = Winsize::default();
+        // SAFETY: TIOCGWINSZ only writes a winsize struct to the pointer
+        let result = unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut winsize as *mut Winsize) };
+        if result == 0 && winsize.ws_col > 0 && winsize.ws_row > 0 {
+            Some((winsize.ws_col as usize, winsize.ws_row as usize))
+        }
+        else {
+            None
+        }
+    }
+    
+    extern "C" fn on_sigwinch(_signum: c_int) {
+        super::RESIZED.store(true, Ordering::SeqCst);
+    }
+    
+    pub fn watch_resize() {
+        // SA_RESTART keeps reads on stdin from failing when the window is
+        // resized
+        #[cfg(target_os = "linux")]
+        let action = SigAction{sa_handler: on_sigwinch, sa_mask: Default::default(), sa_flags: SA_RESTART, sa_restorer: 0};
+        #[cfg(not(target_os = "linux"))]
+        let action = SigAction{sa_handler: on_sigwinch, sa_mask: Default::default(), sa_flags: SA_RESTART};
+        // SAFETY: the handler only stores to an atomic, which is signal safe
+        unsafe {
+            sigaction(SIGWINCH, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+/// Other platforms can't query the terminal, only the environment
+#[cfg(not(any(
+    all(target_os = "linux", any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm", target_arch = "aarch64", target_arch = "riscv64")),
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+)))]
+mod sys {
+    pub fn size() -> Option<(usize, usize)> {
+        None
+    }
+    
+    pub fn watch_resize() {}
+}
+
+/// Get the size of the terminal connected to stdout in cells, as (columns,
+/// rows). Falls back to the `COLUMNS` and `LINES` environment variables, and
+/// returns `None` if the size is unknown
+pub fn size() -> Option<(usize, usize)> {
+    if let Some(size) = sys::size() {
+        return Some(size);
+    }
+    
+    let columns = std::env::var("COLUMNS").ok()?.parse().ok()?;
+    let lines = std::env::var("LINES").ok()?.parse().ok()?;
+    Some((columns, lines))
+}
+
+/// Start watching for terminal resizes (SIGWINCH). Check for them with
+/// `resized`. Only supported on Linux, macOS, iOS and FreeBSD, does nothing
+/// elsewhere
+pub fn watch_resize() {
+    sys::watch_resize();
+}
+
+/// Check whether the terminal was resized since the last call. Needs
+/// `watch_resize` to have been called
+pub fn resized() -> bool {
+    RESIZED.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn detect(vars: &[(&str, &str)]) -> Capabilities {
+        Capabilities::from_env(|key| vars.iter().find(|(name, _)| *name == key).map(|(_, value)| value.to_string()))
+    }
+    
+    #[test]
+    fn color_mode_precedence() {
+        let table: &[(&[(&str, &str)], ColorMode)] = &[
+            (&[], ColorMode::Ansi16),
+            (&[("TERM", "xterm")], ColorMode::Ansi16),
+            (&[("TERM", "xterm-256color")], ColorMode::Ansi256),
+            (&[("TERM", "screen-256color")], ColorMode::Ansi256),
+            (&[("TERM", "xterm-direct")], ColorMode::TrueColor),
+            (&[("TERM", "dumb")], ColorMode::Monochrome),
+            (&[("TERM", "xterm"), ("COLORTERM", "truecolor")], ColorMode::TrueColor),
+            (&[("TERM", "xterm-256color"), ("COLORTERM", "24bit")], ColorMode::TrueColor),
+            (&[("TERM", "xterm-256color"), ("COLORTERM", "yes")], ColorMode::Ansi256),
+            // COLORTERM wins over TERM, even a dumb one
+            (&[("TERM", "dumb"), ("COLORTERM", "truecolor")], ColorMode::TrueColor),
+            // NO_COLOR wins over everything, unless it is empty
+            (&[("TERM", "xterm-256color"), ("COLORTERM", "truecolor"), ("NO_COLOR", "1")], ColorMode::Monochrome),
+            (&[("TERM", "xterm-256color"), ("NO_COLOR", "")], ColorMode::Ansi256),
+        ];
+        for (vars, color_mode) in table {
+            assert_eq!(detect(vars).color_mode, *color_mode, "{:?}", vars);
+        }
+    }
+    
+    #[test]
+    fn glyphs_from_locale() {
+        let table: &[(&[(&str, &str)], GlyphSet)] = &[
+            (&[], GlyphSet::Ascii),
+            (&[("LANG", "C")], GlyphSet::Ascii),
+            (&[("LANG", "en_US.UTF-8")], GlyphSet::Unicode),
+            (&[("LANG", "de_DE.utf8")], GlyphSet::Unicode),
+            // LC_ALL wins over LC_CTYPE, which wins over LANG
+            (&[("LANG", "en_US.UTF-8"), ("LC_CTYPE", "C")], GlyphSet::Ascii),
+            (&[("LANG", "C"), ("LC_CTYPE", "en_US.UTF-8")], GlyphSet::Unicode),
+            (&[("LC_CTYPE", "en_US.UTF-8"), ("LC_ALL", "POSIX")], GlyphSet::Ascii),
+            // Empty variables are skipped
+            (&[("LANG", "en_US.UTF-8"), ("LC_ALL", "")], GlyphSet::Unicode),
+        ];
+        for (vars, glyphs) in table {
+            assert_eq!(detect(vars).glyphs, *glyphs, "{:?}", vars);
+        }
+    }
+    
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn resize_signal() {
+        extern "C" {
+            fn raise(signum: std::os::raw::c_int) -> std::os::raw::c_int;
+        }
+        
+        watch_resize();
+        resized();
+        // SAFETY: SIGWINCH has a handler now, which only sets a flag
+        unsafe {
+            raise(28);
+        }
+        assert!(resized());
+        assert!(!resized());
+    }
+}
@@ -0,0 +1,414 @@
+use crate::color::Color;
+use crate::geometry::Point;
+use crate::image::{Image, ImageError};
+use crate::palette::Palette;
+use crate::terminal::GlyphSet;
+use crate::unicode::char_width;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A single texture cell. Channels set to `None` leave whatever is already
+/// in the framebuffer untouched
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextureCell {
+    pub character: Option<char>,
+    pub fg_color: Option<Color>,
+    pub bg_color: Option<Color>,
+}
+
+/// Cell of empty textures, which leaves the framebuffer untouched
+static EMPTY_CELL: TextureCell = TextureCell{character: None, fg_color: None, bg_color: None};
+
+/// How image pixels become texture cells
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelMapping {
+    /// Pixels set the background color
+    Background,
+    /// Pixels set the foreground color
+    Foreground,
+    /// Pixels set the foreground color and a shade glyph picked by their
+    /// lightness, denser for lighter pixels
+    Shaded(GlyphSet),
+}
+
+/// A texture made of cells, stored row by row. `wrap` is the size of the
+/// texture in world space, after which it repeats
+#[derive(Clone, Debug)]
+pub struct Texture {
+    /// Always `width * height` cells
+    cells: Vec<TextureCell>,
+    width: usize,
+    height: usize,
+    pub wrap: Point,
+}
+
+impl Texture {
+    /// Create a texture from its cells, stored row by row. Returns `None` if
+    /// the amount of cells doesn't match the size
+    pub fn from_cells(width: usize, height: usize, wrap: Point, cells: Vec<TextureCell>) -> Option<Texture> {
+        if width == 0 || height == 0 || cells.len() != width * height {
+            return None;
+        }
+        
+        Some(Texture{
+            cells,
+            width,
+            height,
+            wrap,
+        })
+    }
+    
+    /// Create a texture with a cell for each pixel of an image. Missing
+    /// pixels give empty cells
+    pub fn from_image(image: &Image, wrap: Point, mapping: PixelMapping) -> Texture {
+        let size = image.width * image.height;
+        let mut cells: Vec<TextureCell> = image.pixels.iter().take(size).map(|&color| match mapping {
+            PixelMapping::Background => TextureCell{character: None, fg_color: None, bg_color: Some(color)},
+            PixelMapping::Foreground => TextureCell{character: None, fg_color: Some(color), bg_color: None},
+            PixelMapping::Shaded(glyphs) => {
+                let shades = glyphs.shades();
+                let lightness = (color.to_lab().l / 100.0).clamp(0.0, 1.0);
+                let shade = ((1.0 - lightness) * shades.len() as f64) as usize;
+                TextureCell{character: Some(shades[shade.min(shades.len() - 1)]), fg_color: Some(color), bg_color: None}
+            },
+        }).collect();
+        cells.resize(size, TextureCell::default());
+        
+        Texture{
+            cells,
+            width: image.width,
+            height: image.height,
+            wrap,
+        }
+    }
+    
+    /// Load a texture from an image file. See `Image::parse` for the
+    /// supported formats
+    pub fn from_file<P: AsRef<Path>>(path: P, wrap: Point, mapping: PixelMapping) -> Result<Texture, ImageError> {
+        let image = Image::from_file(path)?;
+        if image.width == 0 || image.height == 0 {
+            return Err(ImageError::Parse("texture images can't be empty".to_string()));
+        }
+        Ok(Texture::from_image(&image, wrap, mapping))
+    }
+    
+    /// Parse a plain text texture. Lines starting with `;` are comments.
+    /// A texture is made of sections:
+    ///
+    /// ```text
+    /// wrap 6 6
+    /// transparent .
+    /// legend
+    /// r fg #ffffff bg #ff0000
+    /// m bg #646464
+    /// glyphs
+    /// |      |
+    /// |      |
+    /// colors
+    /// |mmmmmm|
+    /// |rrrrrm|
+    /// ```
+    ///
+    /// `wrap` is the size of the texture in world space, 1 by 1 if missing.
+    /// Glyph rows sit between `|` and set the cell characters; the
+    /// `transparent` glyph leaves the character untouched. The legend maps
+    /// single character keys to a `fg` and/or `bg` color (`#rrggbb`, `#rgb`
+    /// or `rgb:rr/gg/bb`). Keys come from the optional color grid, which
+    /// must match the glyph grid and uses spaces for cells without colors,
+    /// or from the glyphs themselves if there's no color grid
+    pub fn parse(text: &str) -> Result<Texture, TextureError> {
+        #[derive(PartialEq)]
+        enum Section {
+            Header,
+            Legend,
+            Glyphs,
+            Colors,
+        }
+        
+        let mut section = Section::Header;
+        let mut wrap = Point{x: 1.0, y: 1.0};
+        let mut transparent = None;
+        let mut legend: HashMap<char, (Option<Color>, Option<Color>)> = HashMap::new();
+        // Rows of each grid, with the line and column of their first cell
+        let mut glyph_rows: Vec<(usize, usize, Vec<char>)> = Vec::new();
+        let mut color_rows: Vec<(usize, usize, Vec<char>)> = Vec::new();
+        
+        for (line_index, line) in text.lines().enumerate() {
+            let line_number = line_index + 1;
+            let error = |column: usize, message: String| TextureError::Parse{line: line_number, column, message};
+            // Words with the column they start at
+            let words = text_words(line);
+            let (column, first) = match words.first() {
+                Some(&(_, word)) if word.starts_with(';') => continue,
+                Some(&word) => word,
+                None => continue,
+            };
+            
+            if first.starts_with('|') && (section == Section::Glyphs || section == Section::Colors) {
+                // Rows keep their spaces
+                let row: Vec<char> = line.trim().chars().collect();
+                if row.len() < 2 || row[row.len() - 1] != '|' {
+                    return Err(error(column + row.len(), "expected `|` at the end of the row".to_string()));
+                }
+                let row = row[1..row.len() - 1].to_vec();
+                if let Some(x) = row.iter().position(|&c| char_width(c) != 1) {
+                    return Err(error(column + 1 + x, format!("{:?} is not a single column character", row[x])));
+                }
+                let rows = if section == Section::Glyphs { &mut glyph_rows } else { &mut color_rows };
+                if let Some((_, _, first_row)) = rows.first() {
+                    if row.len() != first_row.len() {
+                        return Err(error(column, format!("row is {} cells wide, but the first row is {}", row.len(), first_row.len())));
+                    }
+                }
+                rows.push((line_number, column + 1, row));
+                continue;
+            }
+            
+            match first {
+                "legend" if words.len() == 1 => section = Section::Legend,
+                "glyphs" if words.len() == 1 => section = Section::Glyphs,
+                "colors" if words.len() == 1 => section = Section::Colors,
+                "wrap" if section == Section::Header => {
+                    let size: Vec<f64> = words[1..].iter().filter_map(|(_, word)| word.parse().ok()).filter(|&size| size > 0.0).collect();
+                    if words.len() != 3 || size.len() != 2 {
+                        return Err(error(column, "expected `wrap <width> <height>` with positive sizes".to_string()));
+                    }
+                    wrap = Point{x: size[0], y: size[1]};
+                },
+                "transparent" if section == Section::Header => {
+                    match words.get(1) {
+                        Some(&(_, glyph)) if words.len() == 2 && glyph.chars().count() == 1 => transparent = glyph.chars().next(),
+                        _ => return Err(error(column, "expected `transparent <glyph>`".to_string())),
+                    }
+                },
+                _ if section == Section::Legend => {
+                    let mut key = first.chars();
+                    let key = match (key.next(), key.next()) {
+                        (Some(key), None) => key,
+                        _ => return Err(error(column, format!("legend keys are single characters, got `{}`", first))),
+                    };
+                    if legend.contains_key(&key) {
+                        return Err(error(column, format!("`{}` is already in the legend", key)));
+                    }
+                    if words.len() == 1 {
+                        return Err(error(column, format!("`{}` needs a `fg` or `bg` color", key)));
+                    }
+                    
+                    let (mut fg, mut bg) = (None, None);
+                    for pair in words[1..].chunks(2) {
+                        let (channel_column, channel) = pair[0];
+                        let target = match channel {
+                            "fg" => &mut fg,
+                            "bg" => &mut bg,
+                            _ => return Err(error(channel_column, format!("expected `fg` or `bg`, got `{}`", channel))),
+                        };
+                        let &(value_column, value) = pair.get(1).ok_or_else(|| error(channel_column, format!("missing color after `{}`", channel)))?;
+                        *target = Some(Palette::parse_color(value).ok_or_else(|| error(value_column, format!("invalid color `{}`", value)))?);
+                    }
+                    legend.insert(key, (fg, bg));
+                },
+                _ => {
+                    let message = match section {
+                        Section::Glyphs | Section::Colors => format!("expected a row between `|`, got `{}`", line.trim()),
+                        _ => format!("unexpected `{}`", first),
+                    };
+                    return Err(error(column, message));
+                },
+            }
+        }
+        
+        let height = glyph_rows.len();
+        let width = match glyph_rows.first() {
+            Some((_, _, row)) if !row.is_empty() => row.len(),
+            _ => return Err(TextureError::Parse{line: text.lines().count().max(1), column: 1, message: "missing glyph rows".to_string()}),
+        };
+        if let Some((line, column, row)) = color_rows.first() {
+            if color_rows.len() != height || row.len() != width {
+                let message = format!("color grid is {}x{}, but the glyph grid is {}x{}", row.len(), color_rows.len(), width, height);
+                return Err(TextureError::Parse{line: *line, column: column - 1, message});
+            }
+        }
+        
+        let mut cells = Vec::with_capacity(width * height);
+        for (y, (_, _, glyphs)) in glyph_rows.iter().enumerate() {
+            for (x, &glyph) in glyphs.iter().enumerate() {
+                let (fg_color, bg_color) = match color_rows.get(y) {
+                    Some((line, column, keys)) => match keys[x] {
+                        ' ' => (None, None),
+                        key => *legend.get(&key).ok_or_else(|| TextureError::Parse{line: *line, column: column + x, message: format!("`{}` is not in the legend", key)})?,
+                    },
+                    None => legend.get(&glyph).copied().unwrap_or((None, None)),
+                };
+                let character = if Some(glyph) == transparent { None } else { Some(glyph) };
+                cells.push(TextureCell{character, fg_color, bg_color});
+            }
+        }
+        
+        Ok(Texture{
+            cells,
+            width,
+            height,
+            wrap,
+        })
+    }
+    
+    /// Load a plain text texture file. See `parse` for the format
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Texture, TextureError> {
+        Texture::parse(&fs::read_to_string(path)?)
+    }
+    
+    /// Create a texture by calling a generator for each cell
+    pub fn from_generator(width: usize, height: usize, wrap: Point, cell_generator: &dyn Fn(usize, usize) -> TextureCell) -> Texture {
+        let mut cells = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                cells.push(cell_generator(x, y));
+            }
+        }
+        
+        Texture{
+            cells,
+            width,
+            height,
+            wrap,
+        }
+    }
+    
+    /// Get the width of the texture in cells
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    
+    /// Get the height of the texture in cells
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    
+    /// Get the cells, row by row
+    pub fn cells(&self) -> &[TextureCell] {
+        &self.cells
+    }
+    
+    /// Get the cells, row by row, to modify them, e.g. to animate the texture
+    pub fn cells_mut(&mut self) -> &mut [TextureCell] {
+        &mut self.cells
+    }
+    
+    /// Get the cell at a world space texture coordinate. Empty textures give
+    /// a cell with every channel set to `None`
+    pub fn get_cell(&self, x: f64, y: f64) -> &TextureCell {
+        // Textures from generators or images can be empty
+        if self.cells.is_empty() {
+            return &EMPTY_CELL;
+        }
+        
+        let x_wrapped:usize = (x.rem_euclid(self.wrap.x) / self.wrap.x * self.width as f64).floor() as usize;
+        let y_wrapped:usize = (y.rem_euclid(self.wrap.y) / self.wrap.y * self.height as f64).floor() as usize;
+        // Rounding can land exactly on the size, clamp to the last cell
+        &self.cells[y_wrapped.min(self.height - 1) * self.width + x_wrapped.min(self.width - 1)]
+    }
+}
+
+/// Split a line into whitespace separated words, with the column each one
+/// starts at (from 1, counting characters)
+fn text_words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (column, (index, c)) in line.char_indices().enumerate() {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some((column + 1, index)),
+            (Some((word_column, word_index)), true) => {
+                words.push((word_column, &line[word_index..index]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some((word_column, word_index)) = start {
+        words.push((word_column, &line[word_index..]));
+    }
+    words
+}
+
+/// Error returned when loading a plain text texture
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    /// Malformed texture. `line` and `column` start at 1
+    Parse{line: usize, column: usize, message: String},
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Io(error) => write!(f, "could not read texture: {}", error),
+            TextureError::Parse{line, column, message} => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Io(error) => Some(error),
+            TextureError::Parse{..} => None,
+        }
+    }
+}
+
+impl From<io::Error> for TextureError {
+    fn from(error: io::Error) -> TextureError {
+        TextureError::Io(error)
+    }
+}
+
+/// Handle to a texture in a `TextureRegistry`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(usize);
+
+/// Owns the textures of a world. Walls refer to them by `TextureId`, so
+/// several walls can share a texture
+#[derive(Clone, Debug, Default)]
+pub struct TextureRegistry {
+    textures: Vec<Texture>,
+}
+
+impl TextureRegistry {
+    pub fn new() -> TextureRegistry {
+        TextureRegistry::default()
+    }
+    
+    /// Add a texture, returning its handle
+    pub fn add(&mut self, texture: Texture) -> TextureId {
+        self.textures.push(texture);
+        TextureId(self.textures.len() - 1)
+    }
+    
+    /// Get a texture by handle. Returns `None` for handles from another
+    /// registry that are out of range
+    pub fn get(&self, id: TextureId) -> Option<&Texture> {
+        self.textures.get(id.0)
+    }
+    
+    /// Get a texture by handle to modify it, e.g. to animate it
+    pub fn get_mut(&mut self, id: TextureId) -> Option<&mut Texture> {
+        self.textures.get_mut(id.0)
+    }
+    
+    /// Replace a texture, keeping its handle. Returns the old texture
+    pub fn replace(&mut self, id: TextureId, texture: Texture) -> Option<Texture> {
+        self.textures.get_mut(id.0).map(|old| std::mem::replace(old, texture))
+    }
+    
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
@@ -0,0 +1,252 @@
+//! Display width of characters in a terminal, and grapheme clusters
+
+use std::fmt;
+
+/// Zero width characters: combining marks, joiners and variation selectors
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2), (0x05C4, 0x05C5), (0x05C7, 0x05C7), (0x0610, 0x061A),
+    (0x064B, 0x065F), (0x0670, 0x0670), (0x06D6, 0x06DC), (0x06DF, 0x06E4),
+    (0x06E7, 0x06E8), (0x06EA, 0x06ED), (0x0900, 0x0902), (0x093A, 0x093A),
+    (0x093C, 0x093C), (0x0941, 0x0948), (0x094D, 0x094D), (0x0951, 0x0957),
+    (0x0E31, 0x0E31), (0x0E34, 0x0E3A), (0x0E47, 0x0E4E), (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF), (0x200B, 0x200F), (0x202A, 0x202E), (0x2060, 0x2064),
+    (0x20D0, 0x20FF), (0x302A, 0x302D), (0x3099, 0x309A), (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F), (0xFEFF, 0xFEFF), (0x1F3FB, 0x1F3FF), (0xE0000, 0xE007F),
+    (0xE0100, 0xE01EF),
+];
+
+/// Wide (two column) characters: East Asian wide and fullwidth characters
+/// and emoji presentation characters
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F), (0x231A, 0x231B), (0x2329, 0x232A), (0x23E9, 0x23EC),
+    (0x23F0, 0x23F0), (0x23F3, 0x23F3), (0x25FD, 0x25FE), (0x2614, 0x2615),
+    (0x2648, 0x2653), (0x267F, 0x267F), (0x2693, 0x2693), (0x26A1, 0x26A1),
+    (0x26AA, 0x26AB), (0x26BD, 0x26BE), (0x26C4, 0x26C5), (0x26CE, 0x26CE),
+    (0x26D4, 0x26D4), (0x26EA, 0x26EA), (0x26F2, 0x26F3), (0x26F5, 0x26F5),
+    (0x26FA, 0x26FA), (0x26FD, 0x26FD), (0x2705, 0x2705), (0x270A, 0x270B),
+    (0x2728, 0x2728), (0x274C, 0x274C), (0x274E, 0x274E), (0x2753, 0x2755),
+    (0x2757, 0x2757), (0x2795, 0x2797), (0x27B0, 0x27B0), (0x27BF, 0x27BF),
+    (0x2B1B, 0x2B1C), (0x2B50, 0x2B50), (0x2B55, 0x2B55), (0x2E80, 0x303E),
+    (0x3041, 0x33FF), (0x3400, 0x4DBF), (0x4E00, 0x9FFF), (0xA000, 0xA4CF),
+    (0xA960, 0xA97F), (0xAC00, 0xD7A3), (0xF900, 0xFAFF), (0xFE10, 0xFE19),
+    (0xFE30, 0xFE6F), (0xFF00, 0xFF60), (0xFFE0, 0xFFE6), (0x16FE0, 0x16FE4),
+    (0x17000, 0x18AFF), (0x1B000, 0x1B2FF), (0x1F004, 0x1F004), (0x1F0CF, 0x1F0CF),
+    (0x1F18E, 0x1F18E), (0x1F191, 0x1F19A), (0x1F200, 0x1F202), (0x1F210, 0x1F23B),
+    (0x1F240, 0x1F248), (0x1F250, 0x1F251), (0x1F260, 0x1F265), (0x1F300, 0x1F320),
+    (0x1F32D, 0x1F335), (0x1F337, 0x1F37C), (0x1F37E, 0x1F393), (0x1F3A0, 0x1F3CA),
+    (0x1F3CF, 0x1F3D3), (0x1F3E0, 0x1F3F0), (0x1F3F4, 0x1F3F4), (0x1F3F8, 0x1F43E),
+    (0x1F440, 0x1F440), (0x1F442, 0x1F4FC), (0x1F4FF, 0x1F53D), (0x1F54B, 0x1F54E),
+    (0x1F550, 0x1F567), (0x1F57A, 0x1F57A), (0x1F595, 0x1F596), (0x1F5A4, 0x1F5A4),
+    (0x1F5FB, 0x1F64F), (0x1F680, 0x1F6C5), (0x1F6CC, 0x1F6CC), (0x1F6D0, 0x1F6D2),
+    (0x1F6D5, 0x1F6D7), (0x1F6EB, 0x1F6EC), (0x1F6F4, 0x1F6FC), (0x1F7E0, 0x1F7EB),
+    (0x1F90C, 0x1F93A), (0x1F93C, 0x1F945), (0x1F947, 0x1F9FF), (0x1FA70, 0x1FAFF),
+    (0x20000, 0x2FFFD), (0x30000, 0x3FFFD),
+];
+
+/// Check whether a character is in a sorted range table
+fn in_table(table: &[(u32, u32)], c: char) -> bool {
+    let c = c as u32;
+    table.binary_search_by(|&(start, end)| {
+        if end < c {
+            std::cmp::Ordering::Less
+        }
+        else if start > c {
+            std::cmp::Ordering::Greater
+        }
+        else {
+            std::cmp::Ordering::Equal
+        }
+    }).is_ok()
+}
+
+/// Check whether a character is a C0 or C1 control character, or DEL
+pub fn is_control(c: char) -> bool {
+    (c as u32) < 0x20 || (0x7F..0xA0).contains(&(c as u32))
+}
+
+/// Get the amount of terminal columns a character takes up: 0 for combining
+/// marks and control characters, 2 for wide characters and 1 otherwise
+pub fn char_width(c: char) -> usize {
+    if is_control(c) || in_table(ZERO_WIDTH, c) {
+        0
+    }
+    else if in_table(WIDE, c) {
+        2
+    }
+    else {
+        1
+    }
+}
+
+/// Get the amount of terminal columns a string takes up
+pub fn str_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Check whether a character is a zero width character that attaches to the
+/// character before it, such as a combining mark
+pub fn is_zero_width(c: char) -> bool {
+    in_table(ZERO_WIDTH, c)
+}
+
+/// Split text into grapheme clusters: a character followed by any zero width
+/// characters attached to it. This is a simplification of Unicode's rules
+/// that is good enough to keep terminal columns in sync
+pub fn graphemes(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let mut chars = rest.char_indices();
+        chars.next()?;
+        let end = chars.find(|&(_, c)| !is_zero_width(c)).map_or(rest.len(), |(i, _)| i);
+        let (cluster, next) = rest.split_at(end);
+        rest = next;
+        Some(cluster)
+    })
+}
+
+/// Maximum size of a grapheme cluster in bytes. Characters past it are
+/// dropped
+pub const GRAPHEME_CAPACITY: usize = 23;
+
+/// A grapheme cluster stored inline, so that cells holding one stay `Copy`.
+/// Clusters starting with a zero width character get a space as their base,
+/// so that they always take up at least one column. Control characters are
+/// replaced with U+FFFD, as terminals would act on them instead of showing
+/// them
+///
+/// The empty grapheme is the continuation marker: the cell covered by the
+/// right half of the wide character to its left
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Grapheme {
+    bytes: [u8; GRAPHEME_CAPACITY],
+    len: u8,
+}
+
+impl Grapheme {
+    /// The continuation marker
+    pub const CONTINUATION: Grapheme = Grapheme{bytes: [0; GRAPHEME_CAPACITY], len: 0};
+    
+    /// Create a grapheme from a cluster, such as one returned by `graphemes`
+    pub fn new(cluster: &str) -> Grapheme {
+        let mut grapheme = Grapheme::CONTINUATION;
+        for c in cluster.chars() {
+            grapheme.push(c);
+        }
+        grapheme
+    }
+    
+    /// Append a character. Returns `false` if it doesn't fit
+    pub fn push(&mut self, c: char) -> bool {
+        let c = if is_control(c) { char::REPLACEMENT_CHARACTER } else { c };
+        if self.len == 0 && is_zero_width(c) {
+            self.push(' ');
+        }
+        
+        let len = self.len as usize;
+        if len + c.len_utf8() > GRAPHEME_CAPACITY {
+            return false;
+        }
+        c.encode_utf8(&mut self.bytes[len..]);
+        self.len += c.len_utf8() as u8;
+        true
+    }
+    
+    /// Get the cluster as a string. Empty for the continuation marker
+    pub fn as_str(&self) -> &str {
+        // Only ever built from whole characters
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap()
+    }
+    
+    /// Get the first character, or `None` for the continuation marker
+    pub fn base(&self) -> Option<char> {
+        self.as_str().chars().next()
+    }
+    
+    /// Check whether this is the continuation marker
+    pub fn is_continuation(&self) -> bool {
+        self.len == 0
+    }
+    
+    /// Get the amount of terminal columns this takes up: 2 for wide
+    /// characters, 0 for the continuation marker and 1 otherwise. Same as
+    /// `char_width` of the base, which is never zero width
+    pub fn width(&self) -> usize {
+        self.base().map_or(0, char_width)
+    }
+}
+
+impl Default for Grapheme {
+    fn default() -> Grapheme {
+        Grapheme::from(' ')
+    }
+}
+
+impl From<char> for Grapheme {
+    fn from(c: char) -> Grapheme {
+        let mut grapheme = Grapheme::CONTINUATION;
+        grapheme.push(c);
+        grapheme
+    }
+}
+
+impl PartialEq<char> for Grapheme {
+    fn eq(&self, other: &char) -> bool {
+        let mut chars = self.as_str().chars();
+        chars.next() == Some(*other) && chars.next().is_none()
+    }
+}
+
+impl fmt::Debug for Grapheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Grapheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn grapheme_width_matches_char_width() {
+        for c in ['a', ' ', '\u{754c}', '\u{1F600}', '\u{2500}'] {
+            assert_eq!(Grapheme::from(c).width(), char_width(c), "{:?}", c);
+        }
+        assert_eq!(Grapheme::new("e\u{301}").width(), 1);
+        assert_eq!(Grapheme::new("\u{754c}\u{301}").width(), 2);
+        assert_eq!(Grapheme::CONTINUATION.width(), 0);
+    }
+    
+    #[test]
+    fn zero_width_bases() {
+        let grapheme = Grapheme::new("\u{301}");
+        assert_eq!(grapheme.as_str(), " \u{301}");
+        assert_eq!(grapheme.width(), char_width(grapheme.base().unwrap()));
+    }
+    
+    #[test]
+    fn controls_are_replaced() {
+        for c in ['\0', '\t', '\n', '\u{1b}', '\u{7f}', '\u{85}', '\u{9b}'] {
+            assert!(is_control(c));
+            assert_eq!(char_width(c), 0);
+            let grapheme = Grapheme::from(c);
+            assert_eq!(grapheme, char::REPLACEMENT_CHARACTER, "{:?}", c);
+            assert_eq!(grapheme.width(), 1);
+        }
+        assert_eq!(Grapheme::new("a\u{1b}").as_str(), "a\u{FFFD}");
+        assert!(!is_control(' ') && !is_control('\u{a0}'));
+    }
+    
+    #[test]
+    fn clusters() {
+        let clusters: Vec<&str> = graphemes("e\u{301}x\u{754c}\u{FE0F}").collect();
+        assert_eq!(clusters, ["e\u{301}", "x", "\u{754c}\u{FE0F}"]);
+        assert_eq!(str_width("e\u{301}x\u{754c}"), 4);
+    }
+}
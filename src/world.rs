@@ -0,0 +1,185 @@
+use crate::framebuffer::Framebuffer;
+use crate::geometry::{Point, Line};
+use crate::terminal::GlyphSet;
+use crate::texture::{TextureId, TextureRegistry};
+use std::f64::consts::PI;
+use crate::color::Color;
+
+/// How walls are shaded by the angle they are seen at
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WallShading {
+    /// Shade glyphs drawn over the texture background colors. Texture glyphs
+    /// and foreground colors are ignored
+    #[default]
+    Glyphs,
+    /// Texture glyphs and colors are drawn as they are, darkened by the angle
+    /// and distance walls are seen at
+    Colors,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Wall {
+    pub line: Line,
+    /// Texture in the world's `textures`
+    pub texture: TextureId,
+    /// Height in world units, from the floor up. The camera's eye level is
+    /// half of `Wall::DEFAULT_HEIGHT`
+    pub height: f64,
+}
+
+impl Wall {
+    /// Height of a wall whose top is as far above the eye level as the
+    /// floor is below it
+    pub const DEFAULT_HEIGHT: f64 = 16.0;
+}
+
+#[derive(Debug)]
+pub struct World {
+    pub framebuffer: Framebuffer,
+    pub glyphs: GlyphSet,
+    /// Textures used by the walls
+    pub textures: TextureRegistry,
+    pub shading: WallShading,
+    pub walls: Vec<Wall>,
+    pub pos: Point,
+    pub cam_rot: f64,
+    pub cam_hfov: f64,
+    pub cam_range: f64,
+}
+
+impl World {
+    fn intersect_nearest(&self, angle: f64, cam_normal: Point) -> Option<(Point, Wall, f64, f64)> {
+        let cam_line = Line::from_angle(self.pos, self.cam_range, angle);
+        
+        let mut intersection:Option<(Point, Wall, f64, f64)> = None;
+        let mut best_dist:f64 = self.cam_range;
+        for wall in &self.walls {
+            let this_intersection = cam_line.intersection(wall.line);
+            if let Some(point) = this_intersection {
+                let this_dist = (point - self.pos).magnitude();
+                if this_dist < best_dist {
+                    let wall_normal = wall.line.normal();
+                    let mut intersection_angle = cam_normal.dot(wall_normal).acos();
+                    if intersection_angle > PI * 0.5 {
+                        intersection_angle = PI - intersection_angle;
+                    }
+                    let intersection_dist = (point - wall.line.start).magnitude();
+                    intersection = Some((point, *wall, intersection_angle, intersection_dist));
+                    best_dist = this_dist;
+                }
+            }
+        }
+        
+        intersection
+    }
+
+    /// Fill a cell, or a pixel in half-block mode, with a plain color
+    fn fill(&mut self, x: usize, y: usize, color: Color) {
+        if self.framebuffer.half_block() {
+            self.framebuffer.set_pixel(x, y, color);
+        }
+        else {
+            self.framebuffer.set_cell(x, y, Some(' '), Some(Color{r: 255,g: 255,b: 255}), Some(color));
+        }
+    }
+    
+    /// Render the world into the framebuffer. Present the framebuffer
+    /// afterwards to show the frame
+    ///
+    /// One ray is cast per column and wall heights scale with the amount of
+    /// rows, so rendering follows the framebuffer if it is resized
+    ///
+    /// In half-block mode rays are cast at double vertical resolution and
+    /// shading darkens the wall background colors instead of using shade
+    /// glyphs
+    pub fn render(&mut self) {
+        //self.framebuffer.clear(' ', Color{r: 255,g: 255,b: 255}, Color{r: 0,g: 0,b: 0});
+        const SHADE_COLOR:Color = Color{r:0, g:0, b:0};
+        // How much of the shade color each shade glyph shows
+        const SHADE_AMOUNTS:[f64; 4] = [0.75, 0.5, 0.25, 0.0];
+        // How much of the shade color is added at the end of the camera range
+        // when shading with colors
+        const DISTANCE_SHADE:f64 = 0.5;
+        let shades = self.glyphs.shades();
+        let half_block = self.framebuffer.half_block();
+        let rows = if half_block { self.framebuffer.pixel_height() } else { self.framebuffer.height };
+        let angle_start = self.cam_rot - self.cam_hfov * 0.5;
+        let angle_step = self.cam_hfov / self.framebuffer.width as f64;
+        let v_mid:usize = rows / 2;
+        let cam_normal = Point::from_normal(self.cam_rot, 1.0);
+        for x in 0..self.framebuffer.width {
+            let floor_h = rows / 2;
+            for y in 0..floor_h {
+                self.fill(x, y, Color{r: 100,g: 100,b: 100});
+            }
+            for y in floor_h..rows {
+                self.fill(x, y, Color{r: 127,g: 127,b: 0});
+            }
+        
+            let angle = angle_start + x as f64 * angle_step;
+            let intersection = self.intersect_nearest(angle, cam_normal);
+            
+            if let Some((point, wall, intersection_angle, intersection_dist)) = intersection {
+                // Walls with a missing texture aren't drawn
+                let texture = match self.textures.get(wall.texture) {
+                    Some(texture) => texture,
+                    None => continue,
+                };
+                let dist = (point - self.pos).magnitude() * (self.cam_rot - angle).cos();
+                let range_percent = dist / self.cam_range;
+                
+                if (0.0..1.0).contains(&range_percent) {
+                    let shade:usize;
+                    if intersection_angle > 1.30899694 {
+                        // > 75 degrees
+                        shade = 3;
+                    }
+                    else if intersection_angle > 0.73303829 {
+                        // > 42 degrees
+                        shade = 2;
+                    }
+                    else {
+                        // <= 42 degrees
+                        shade = 1;
+                    }
+                    
+                    // XXX I double tan'ed here to help with the warp on diagonal walls, but I have no idea why it works... huh
+                    // This is the height on screen of a wall of the default height
+                    let default_height:usize = ((1.0 - range_percent).tan().tan() * rows as f64).floor() as usize;
+                    if default_height >= 1 && wall.height > 0.0 {
+                        // Walls stand on the floor, and grow up from it
+                        let rows_per_unit = default_height as f64 / Wall::DEFAULT_HEIGHT;
+                        let floor_y = v_mid + default_height - default_height / 2;
+                        let top_y = floor_y as f64 - wall.height * rows_per_unit;
+                        // Cells are drawn if their center is on the wall
+                        let min = (top_y - 0.5).ceil().max(0.0) as usize;
+                        let max = floor_y.min(rows);
+                        let shade_amount = match self.shading {
+                            WallShading::Glyphs => SHADE_AMOUNTS[shade],
+                            WallShading::Colors => 1.0 - (1.0 - SHADE_AMOUNTS[shade]) * (1.0 - range_percent * DISTANCE_SHADE),
+                        };
+                        let darken = |color: Color| color.mix(&SHADE_COLOR, shade_amount);
+                        for h in min..max {
+                            // V is the height above the floor in world units, negated
+                            // as textures go down, so texture rows line up with the
+                            // floor on walls of any height
+                            let v = (h as f64 + 0.5 - floor_y as f64) / rows_per_unit;
+                            let texture_cell = texture.get_cell(intersection_dist, v);
+                            if half_block {
+                                if let Some(color) = texture_cell.bg_color {
+                                    self.framebuffer.set_pixel(x, h, darken(color));
+                                }
+                            }
+                            else if self.shading == WallShading::Glyphs {
+                                self.framebuffer.set_cell(x, h, Some(shades[shade]), Some(SHADE_COLOR), texture_cell.bg_color);
+                            }
+                            else {
+                                self.framebuffer.set_cell(x, h, texture_cell.character, texture_cell.fg_color.map(darken), texture_cell.bg_color.map(darken));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
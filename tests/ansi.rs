@@ -0,0 +1,151 @@
+//! Presented frames parsed back with `Framebuffer::from_ansi` must match the
+//! framebuffer they came from, in every color mode
+
+use console_render::ansi::AnsiParser;
+use console_render::attributes::Attributes;
+use console_render::color::{Color, ColorMetric};
+use console_render::csi_color::{xterm256_color, xterm256_index, ColorMode};
+use console_render::framebuffer::Framebuffer;
+use console_render::palette::Palette;
+
+const WIDTH: usize = 12;
+const HEIGHT: usize = 3;
+
+/// Draw text with attributes, a wide character and a combining mark, using
+/// colors picked from a list
+fn scene(colors: &[Color]) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    framebuffer.clear(' ', colors[0], colors[1]);
+    for (x, c) in "Bold".chars().enumerate() {
+        framebuffer.set_styled_cell(x, 0, Some(c), Some(colors[2]), Some(colors[1]), Some(Attributes::BOLD));
+    }
+    for (x, c) in "dim+ul".chars().enumerate() {
+        framebuffer.set_styled_cell(5 + x, 0, Some(c), Some(colors[3]), Some(colors[4]), Some(Attributes::DIM | Attributes::UNDERLINE));
+    }
+    framebuffer.set_styled_cell(2, 1, Some('\u{754c}'), Some(colors[5]), Some(colors[0]), Some(Attributes::REVERSE));
+    framebuffer.set_grapheme(6, 1, Some("e\u{301}"), Some(colors[2]), Some(colors[3]));
+    for (x, c) in "last row".chars().enumerate() {
+        framebuffer.set_styled_cell(4 + x, 2, Some(c), Some(colors[4]), Some(colors[5]), Some(Attributes::ITALIC | Attributes::STRIKETHROUGH));
+    }
+    framebuffer
+}
+
+/// Change a few cells, for a differential second frame
+fn change(framebuffer: &mut Framebuffer, colors: &[Color]) {
+    framebuffer.set_styled_cell(1, 0, Some('X'), Some(colors[3]), Some(colors[2]), Some(Attributes::NONE));
+    framebuffer.set_styled_cell(4, 1, Some('\u{754c}'), Some(colors[2]), Some(colors[4]), Some(Attributes::BOLD));
+    framebuffer.set_styled_cell(11, 2, Some('!'), Some(colors[0]), Some(colors[1]), Some(Attributes::BLINK));
+}
+
+/// Check that the parser's cells match the framebuffer, with colors as the
+/// color mode sends them
+fn assert_matches(parser: &AnsiParser, framebuffer: &Framebuffer, expected_color: &dyn Fn(Color, bool) -> Color, frame: &str) {
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let expected = framebuffer.get_cell(x, y).unwrap();
+            let actual = parser.framebuffer().get_cell(x, y).unwrap();
+            let at = format!("{} frame, cell ({}, {})", frame, x, y);
+            assert_eq!(actual.grapheme, expected.grapheme, "{}", at);
+            assert_eq!(actual.attributes, expected.attributes, "{}", at);
+            assert_eq!(actual.fg, expected_color(expected.fg, false), "{} fg", at);
+            assert_eq!(actual.bg, expected_color(expected.bg, true), "{} bg", at);
+        }
+    }
+}
+
+fn round_trip(color_mode: ColorMode, colors: &[Color], expected_color: &dyn Fn(Color, bool) -> Color) {
+    let mut framebuffer = scene(colors);
+    framebuffer.set_color_mode(color_mode);
+    // Presenting leaves the cursor below the frame, which needs a row
+    let mut parser = AnsiParser::new(WIDTH, HEIGHT + 1);
+    
+    parser.feed(framebuffer.get_string().unwrap().as_bytes());
+    assert_matches(&parser, &framebuffer, expected_color, "first");
+    
+    change(&mut framebuffer, colors);
+    let second = framebuffer.get_string().unwrap();
+    assert!(!second.contains("\x1b[2J"), "second frame should be differential");
+    parser.feed(second.as_bytes());
+    assert_matches(&parser, &framebuffer, expected_color, "second");
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color{r, g, b}
+}
+
+#[test]
+fn true_color() {
+    let colors = [rgb(1, 2, 3), rgb(250, 240, 230), rgb(12, 200, 99), rgb(77, 0, 130), rgb(255, 128, 0), rgb(9, 90, 180)];
+    round_trip(ColorMode::TrueColor, &colors, &|color, _| color);
+}
+
+#[test]
+fn ansi256() {
+    let colors = [rgb(1, 2, 3), rgb(250, 240, 230), rgb(12, 200, 99), rgb(77, 0, 130), rgb(255, 128, 0), rgb(128, 128, 128)];
+    round_trip(ColorMode::Ansi256, &colors, &|color, _| xterm256_color(xterm256_index(&color, ColorMetric::default())));
+}
+
+#[test]
+fn ansi16() {
+    // Palette entries, including bright ones, which come back exactly
+    let palette = Palette::default().colors;
+    let colors = [palette[0], palette[15], palette[2], palette[9], palette[4], palette[14]];
+    round_trip(ColorMode::Ansi16, &colors, &|color, _| color);
+}
+
+#[test]
+fn monochrome() {
+    let colors = [rgb(1, 2, 3), rgb(250, 240, 230), rgb(12, 200, 99), rgb(77, 0, 130), rgb(255, 128, 0), rgb(9, 90, 180)];
+    let parser = AnsiParser::new(1, 1);
+    let (default_fg, default_bg) = (parser.default_fg, parser.default_bg);
+    round_trip(ColorMode::Monochrome, &colors, &|_, background| if background { default_bg } else { default_fg });
+}
+
+#[test]
+fn cursor_moves_are_clamped() {
+    // ANSI art moves right with a large count to reach the last column
+    let framebuffer = Framebuffer::from_ansi(10, 4, b"\x1b[999Ca\x1b[999;999Hb\x1b[1;1H\x1b[999Bc\x1b[2;999Hd\x1b[3;1H\x1b[999Ge");
+    let cell = |x, y| framebuffer.get_cell(x, y).unwrap().grapheme.to_string();
+    assert_eq!(cell(9, 0), "a");
+    assert_eq!(cell(9, 3), "b");
+    assert_eq!(cell(0, 3), "c");
+    assert_eq!(cell(9, 1), "d");
+    assert_eq!(cell(9, 2), "e");
+    
+    let mut parser = AnsiParser::new(10, 4);
+    parser.feed(b"\x1b[2;3H\x1b[999A\x1b[999D");
+    assert_eq!(parser.cursor(), (0, 0));
+    parser.feed(b"\x1b[999d");
+    assert_eq!(parser.cursor(), (0, 3));
+}
+
+#[test]
+fn colon_sgr_colors() {
+    let mut parser = AnsiParser::new(4, 1);
+    parser.feed(b"\x1b[38:2::10:20:30;48:2:40:50:60ma\x1b[38:5:196;4:3mb\x1b[4:0;48;5;21mc");
+    let framebuffer = parser.framebuffer();
+    let a = framebuffer.get_cell(0, 0).unwrap();
+    assert_eq!((a.fg, a.bg), (rgb(10, 20, 30), rgb(40, 50, 60)));
+    let b = framebuffer.get_cell(1, 0).unwrap();
+    assert_eq!(b.fg, xterm256_color(196));
+    assert!(b.attributes.contains(Attributes::UNDERLINE));
+    let c = framebuffer.get_cell(2, 0).unwrap();
+    assert_eq!(c.bg, xterm256_color(21));
+    assert!(!c.attributes.contains(Attributes::UNDERLINE));
+}
+
+#[test]
+fn huge_counts_and_parameters() {
+    // Would scroll for a very long time without a limit
+    let framebuffer = Framebuffer::from_ansi(4, 2, b"ab\r\ncd\x1b[999999999Sx");
+    let text: String = (0..2).flat_map(|y| (0..4).map(move |x| (x, y))).map(|(x, y)| framebuffer.get_cell(x, y).unwrap().grapheme.to_string()).collect();
+    assert_eq!(text, "      x ");
+    
+    // A long run of digits is cut off instead of growing without limit
+    let mut parser = AnsiParser::new(4, 1);
+    let mut stream = b"\x1b[".to_vec();
+    stream.extend(std::iter::repeat_n(b'9', 1_000_000));
+    stream.extend_from_slice(b"Cy");
+    parser.feed(&stream);
+    assert_eq!(parser.framebuffer().get_cell(3, 0).unwrap().grapheme.to_string(), "y");
+}
@@ -0,0 +1,403 @@
+//! Golden frame tests for `World::render`. Each test builds a world from a
+//! fixture in `tests/fixtures`, renders one frame and compares the cells with
+//! a snapshot in `tests/snapshots`. Run with `BLESS=1` to write the snapshots
+//! from the current output instead
+//!
+//! Fixtures are line based, with `# ` starting comment lines:
+//!
+//! ```text
+//! size <width> <height>
+//! half_block <true|false>
+//! glyphs <unicode|ascii>
+//! shading <glyphs|colors>
+//! camera <x> <y> <rotation in degrees> <horizontal fov in degrees> <range>
+//! texture <name> <width> <height> <wrap x> <wrap y>
+//! <height rows of width cells, each `#rrggbb` or `.` for no color>
+//! texture_image <name> <file in tests/fixtures> <wrap x> <wrap y>
+//! texture_text <name> <file in tests/fixtures>
+//! wall <start x> <start y> <end x> <end y> <texture name> [height]
+//! ```
+#![cfg(feature = "raycaster")]
+
+use console_render::color::Color;
+use console_render::framebuffer::Framebuffer;
+use console_render::geometry::{Line, Point};
+use console_render::terminal::GlyphSet;
+use console_render::texture::{PixelMapping, Texture, TextureCell, TextureId, TextureRegistry};
+use console_render::unicode::{graphemes, Grapheme};
+use console_render::world::{Wall, WallShading, World};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable that makes tests write their snapshots
+const BLESS_VAR: &str = "BLESS";
+
+/// Symbols used for colors in snapshots
+const SYMBOLS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!\"#$%&'()*+,-./:;<=>?@[\\]^_`{}~";
+
+/// Most differing cells listed when a snapshot doesn't match
+const MAX_LISTED_CELLS: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+struct SnapshotCell {
+    grapheme: String,
+    fg: Color,
+    bg: Color,
+}
+
+/// The cells of a rendered frame
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    width: usize,
+    height: usize,
+    cells: Vec<SnapshotCell>,
+}
+
+fn hex(color: Color) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn parse_hex(text: &str) -> Option<Color> {
+    let digits = text.strip_prefix('#').filter(|digits| digits.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some(Color{r: channel(0)?, g: channel(2)?, b: channel(4)?})
+}
+
+impl Snapshot {
+    fn from_framebuffer(framebuffer: &Framebuffer) -> Snapshot {
+        let mut cells = Vec::new();
+        for y in 0..framebuffer.height {
+            for x in 0..framebuffer.width {
+                let cell = framebuffer.get_cell(x, y).unwrap();
+                cells.push(SnapshotCell{grapheme: cell.grapheme.to_string(), fg: cell.fg, bg: cell.bg});
+            }
+        }
+        Snapshot{width: framebuffer.width, height: framebuffer.height, cells}
+    }
+    
+    /// Format the snapshot: the size, a legend of color symbols, then grids
+    /// of characters, foreground colors and background colors
+    fn to_text(&self) -> String {
+        let mut colors: Vec<Color> = Vec::new();
+        for cell in &self.cells {
+            for color in [cell.fg, cell.bg] {
+                if !colors.contains(&color) {
+                    colors.push(color);
+                }
+            }
+        }
+        
+        // One symbol per color if they fit, two otherwise
+        let symbols: Vec<char> = SYMBOLS.chars().collect();
+        let key_len = if colors.len() <= symbols.len() { 1 } else { 2 };
+        let key = |index: usize| -> String {
+            if key_len == 1 {
+                symbols[index].to_string()
+            }
+            else {
+                format!("{}{}", symbols[index / symbols.len()], symbols[index % symbols.len()])
+            }
+        };
+        let keys: HashMap<Color, String> = colors.iter().enumerate().map(|(i, &color)| (color, key(i))).collect();
+        
+        let mut text = format!("size {} {}\nlegend\n", self.width, self.height);
+        for (i, &color) in colors.iter().enumerate() {
+            writeln!(text, "{} {}", key(i), hex(color)).unwrap();
+        }
+        text.push_str("chars\n");
+        for row in self.cells.chunks(self.width) {
+            let line: String = row.iter().map(|cell| cell.grapheme.as_str()).collect();
+            writeln!(text, "|{}|", line).unwrap();
+        }
+        for (name, channel) in [("fg", 0), ("bg", 1)] {
+            writeln!(text, "{}", name).unwrap();
+            for row in self.cells.chunks(self.width) {
+                let line: String = row.iter().map(|cell| keys[if channel == 0 { &cell.fg } else { &cell.bg }].as_str()).collect();
+                writeln!(text, "|{}|", line).unwrap();
+            }
+        }
+        text
+    }
+    
+    fn parse(text: &str) -> Result<Snapshot, String> {
+        let mut lines = text.lines();
+        let size: Vec<usize> = lines.next().and_then(|line| line.strip_prefix("size "))
+            .ok_or("expected `size`")?
+            .split_whitespace().map(|n| n.parse().map_err(|_| "invalid size")).collect::<Result<_, _>>()?;
+        let (width, height) = match size.as_slice() {
+            [width, height] => (*width, *height),
+            _ => return Err("expected `size <width> <height>`".to_string()),
+        };
+        
+        if lines.next() != Some("legend") {
+            return Err("expected `legend`".to_string());
+        }
+        let mut legend = HashMap::new();
+        let mut line = lines.next();
+        while let Some(entry) = line.filter(|&line| line != "chars") {
+            let (key, color) = entry.rsplit_once(' ').ok_or_else(|| format!("invalid legend entry `{}`", entry))?;
+            legend.insert(key.to_string(), parse_hex(color).ok_or_else(|| format!("invalid color `{}`", color))?);
+            line = lines.next();
+        }
+        let key_len = legend.keys().next().map_or(1, |key| key.chars().count());
+        
+        let char_rows = rows(&mut lines, height, "chars")?;
+        
+        let mut cells = Vec::with_capacity(width * height);
+        for row in &char_rows {
+            for cluster in graphemes(row) {
+                let wide = Grapheme::new(cluster).width() == 2;
+                let placeholder = SnapshotCell{grapheme: cluster.to_string(), fg: Color{r: 0, g: 0, b: 0}, bg: Color{r: 0, g: 0, b: 0}};
+                cells.push(placeholder.clone());
+                if wide {
+                    cells.push(SnapshotCell{grapheme: String::new(), ..placeholder});
+                }
+            }
+        }
+        if cells.len() != width * height {
+            return Err("character rows don't match the size".to_string());
+        }
+        
+        for (name, channel) in [("fg", 0), ("bg", 1)] {
+            if lines.next() != Some(name) {
+                return Err(format!("expected `{}`", name));
+            }
+            let color_rows = rows(&mut lines, height, name)?;
+            for (y, row) in color_rows.iter().enumerate() {
+                let keys: Vec<char> = row.chars().collect();
+                if keys.len() != width * key_len {
+                    return Err(format!("{} row {} doesn't match the size", name, y));
+                }
+                for (x, key) in keys.chunks(key_len).enumerate() {
+                    let key: String = key.iter().collect();
+                    let color = *legend.get(&key).ok_or_else(|| format!("unknown color `{}`", key))?;
+                    let cell = &mut cells[y * width + x];
+                    if channel == 0 { cell.fg = color } else { cell.bg = color }
+                }
+            }
+        }
+        
+        Ok(Snapshot{width, height, cells})
+    }
+    
+    /// Describe how another snapshot differs from this one
+    fn diff(&self, actual: &Snapshot) -> String {
+        if (self.width, self.height) != (actual.width, actual.height) {
+            return format!("size differs: expected {}x{}, got {}x{}\n", self.width, self.height, actual.width, actual.height);
+        }
+        
+        let mut text = String::from("characters, rows with differing cells marked with `>` (expected | actual):\n");
+        let rows = self.cells.chunks(self.width).zip(actual.cells.chunks(self.width));
+        for (expected, got) in rows {
+            let marker = if expected == got { ' ' } else { '>' };
+            let line = |row: &[SnapshotCell]| row.iter().map(|cell| cell.grapheme.as_str()).collect::<String>();
+            writeln!(text, "{} |{}| |{}|", marker, line(expected), line(got)).unwrap();
+        }
+        
+        let differing: Vec<usize> = (0..self.cells.len()).filter(|&i| self.cells[i] != actual.cells[i]).collect();
+        writeln!(text, "{} cells differ:", differing.len()).unwrap();
+        for &i in differing.iter().take(MAX_LISTED_CELLS) {
+            let describe = |cell: &SnapshotCell| format!("{:?} fg {} bg {}", cell.grapheme, hex(cell.fg), hex(cell.bg));
+            writeln!(text, "  ({}, {}): expected {}, got {}", i % self.width, i / self.width, describe(&self.cells[i]), describe(&actual.cells[i])).unwrap();
+        }
+        if differing.len() > MAX_LISTED_CELLS {
+            writeln!(text, "  ...").unwrap();
+        }
+        text
+    }
+}
+
+/// Read `height` grid rows, stripping the `|` around them
+fn rows<'a>(lines: &mut impl Iterator<Item = &'a str>, height: usize, name: &str) -> Result<Vec<String>, String> {
+    (0..height).map(|_| {
+        let line = lines.next().ok_or_else(|| format!("missing {} rows", name))?;
+        line.strip_prefix('|').and_then(|line| line.strip_suffix('|')).map(str::to_string).ok_or_else(|| format!("invalid row `{}`", line))
+    }).collect()
+}
+
+/// A world described by a fixture
+struct Fixture {
+    width: usize,
+    height: usize,
+    half_block: bool,
+    glyphs: GlyphSet,
+    shading: WallShading,
+    pos: Point,
+    cam_rot: f64,
+    cam_hfov: f64,
+    cam_range: f64,
+    /// Name and texture of each texture
+    textures: Vec<(String, Texture)>,
+    /// Line, texture name and height of each wall
+    walls: Vec<(Line, String, f64)>,
+}
+
+impl Fixture {
+    fn parse(text: &str) -> Result<Fixture, String> {
+        let mut fixture = Fixture{
+            width: 0,
+            height: 0,
+            half_block: false,
+            glyphs: GlyphSet::Unicode,
+            shading: WallShading::Glyphs,
+            pos: Point{x: 0.0, y: 0.0},
+            cam_rot: 0.0,
+            cam_hfov: 90.0_f64.to_radians(),
+            cam_range: 30.0,
+            textures: Vec::new(),
+            walls: Vec::new(),
+        };
+        
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with("# "));
+        while let Some(line) = lines.next() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| -> Result<f64, String> {
+                words.get(i).and_then(|word| word.parse().ok()).ok_or_else(|| format!("expected a number in `{}`", line))
+            };
+            
+            match words[0] {
+                "size" => {
+                    fixture.width = number(1)? as usize;
+                    fixture.height = number(2)? as usize;
+                },
+                "half_block" => fixture.half_block = words.get(1) == Some(&"true"),
+                "glyphs" => fixture.glyphs = if words.get(1) == Some(&"ascii") { GlyphSet::Ascii } else { GlyphSet::Unicode },
+                "shading" => fixture.shading = if words.get(1) == Some(&"colors") { WallShading::Colors } else { WallShading::Glyphs },
+                "camera" => {
+                    fixture.pos = Point{x: number(1)?, y: number(2)?};
+                    fixture.cam_rot = number(3)?.to_radians();
+                    fixture.cam_hfov = number(4)?.to_radians();
+                    fixture.cam_range = number(5)?;
+                },
+                "texture" => {
+                    let name = words.get(1).ok_or("texture needs a name")?.to_string();
+                    let (width, height) = (number(2)? as usize, number(3)? as usize);
+                    let wrap = Point{x: number(4)?, y: number(5)?};
+                    let mut cells = Vec::new();
+                    for _ in 0..height {
+                        let row = lines.next().ok_or_else(|| format!("missing rows in texture `{}`", name))?;
+                        for token in row.split_whitespace() {
+                            let bg_color = if token == "." { None } else { Some(parse_hex(token).ok_or_else(|| format!("invalid color `{}`", token))?) };
+                            cells.push(TextureCell{character: None, fg_color: None, bg_color});
+                        }
+                    }
+                    let texture = Texture::from_cells(width, height, wrap, cells).ok_or_else(|| format!("texture `{}` doesn't match its size", name))?;
+                    fixture.textures.push((name, texture));
+                },
+                "texture_image" => {
+                    let name = words.get(1).ok_or("texture needs a name")?.to_string();
+                    let path = fixture_dir().join(words.get(2).ok_or("texture needs an image file")?);
+                    let wrap = Point{x: number(3)?, y: number(4)?};
+                    let texture = Texture::from_file(&path, wrap, PixelMapping::Background).map_err(|error| format!("{}: {}", path.display(), error))?;
+                    fixture.textures.push((name, texture));
+                },
+                "texture_text" => {
+                    let name = words.get(1).ok_or("texture needs a name")?.to_string();
+                    let path = fixture_dir().join(words.get(2).ok_or("texture needs a file")?);
+                    let texture = Texture::from_text_file(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+                    fixture.textures.push((name, texture));
+                },
+                "wall" => {
+                    let line = Line{start: Point{x: number(1)?, y: number(2)?}, end: Point{x: number(3)?, y: number(4)?}};
+                    let texture = words.get(5).ok_or("wall needs a texture")?.to_string();
+                    let height = if words.len() > 6 { number(6)? } else { Wall::DEFAULT_HEIGHT };
+                    fixture.walls.push((line, texture, height));
+                },
+                other => return Err(format!("unknown fixture line `{}`", other)),
+            }
+        }
+        
+        Ok(fixture)
+    }
+    
+    /// Build the world and render one frame
+    fn render(&self) -> Snapshot {
+        let mut textures = TextureRegistry::new();
+        let ids: HashMap<&str, TextureId> = self.textures.iter().map(|(name, texture)| (name.as_str(), textures.add(texture.clone()))).collect();
+        
+        let mut framebuffer = Framebuffer::new(self.width, self.height);
+        framebuffer.set_half_block(self.half_block);
+        let mut world = World{
+            framebuffer,
+            glyphs: self.glyphs,
+            textures,
+            shading: self.shading,
+            walls: self.walls.iter().map(|(line, texture, height)| Wall{line: *line, texture: ids[texture.as_str()], height: *height}).collect(),
+            pos: self.pos,
+            cam_rot: self.cam_rot,
+            cam_hfov: self.cam_hfov,
+            cam_range: self.cam_range,
+        };
+        world.render();
+        Snapshot::from_framebuffer(&world.framebuffer)
+    }
+}
+
+fn fixture_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures")
+}
+
+/// Render a fixture and compare it with its snapshot, or write the snapshot
+/// if blessing
+fn check(name: &str) {
+    let fixture_path = fixture_dir().join(format!("{}.world", name));
+    let snapshot_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots").join(format!("{}.snap", name));
+    
+    let fixture_text = fs::read_to_string(&fixture_path).unwrap_or_else(|error| panic!("{}: {}", fixture_path.display(), error));
+    let fixture = Fixture::parse(&fixture_text).unwrap_or_else(|error| panic!("{}: {}", fixture_path.display(), error));
+    let actual = fixture.render();
+    
+    if std::env::var_os(BLESS_VAR).is_some() {
+        fs::write(&snapshot_path, actual.to_text()).unwrap_or_else(|error| panic!("{}: {}", snapshot_path.display(), error));
+        return;
+    }
+    
+    let expected_text = fs::read_to_string(&snapshot_path)
+        .unwrap_or_else(|error| panic!("{}: {} (run with {}=1 to create it)", snapshot_path.display(), error, BLESS_VAR));
+    let expected = Snapshot::parse(&expected_text).unwrap_or_else(|error| panic!("{}: {}", snapshot_path.display(), error));
+    if expected != actual {
+        panic!("snapshot `{}` doesn't match (run with {}=1 to update it)\n{}", name, BLESS_VAR, expected.diff(&actual));
+    }
+}
+
+#[test]
+fn square_room() {
+    check("square_room");
+}
+
+#[test]
+fn square_room_half_block() {
+    check("square_room_half_block");
+}
+
+#[test]
+fn corner_ascii() {
+    check("corner_ascii");
+}
+
+#[test]
+fn out_of_range() {
+    check("out_of_range");
+}
+
+#[test]
+fn image_texture() {
+    check("image_texture");
+}
+
+#[test]
+fn text_texture_colors() {
+    check("text_texture_colors");
+}
+
+#[test]
+fn text_texture_colors_half_block() {
+    check("text_texture_colors_half_block");
+}
+
+#[test]
+fn wall_heights() {
+    check("wall_heights");
+}
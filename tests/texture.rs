@@ -0,0 +1,174 @@
+//! Loading textures from the image files in `tests/fixtures`
+
+use console_render::color::Color;
+use console_render::geometry::Point;
+use console_render::image::{Image, ImageError};
+use console_render::terminal::GlyphSet;
+use console_render::texture::{PixelMapping, Texture, TextureCell, TextureError};
+use std::path::PathBuf;
+
+const BRICK: Color = Color{r: 255, g: 0, b: 0};
+const MORTAR: Color = Color{r: 100, g: 100, b: 100};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// The demo's bricks, as drawn by its texture generator
+fn bricks() -> Image {
+    let mut image = Image::new(6, 6, BRICK);
+    for y in 0..6 {
+        for x in 0..6 {
+            if y == 0 || y == 3 || (y < 3 && x == 5) || (y > 3 && x == 2) {
+                image.set_pixel(x, y, MORTAR);
+            }
+        }
+    }
+    image
+}
+
+#[test]
+fn ppm() {
+    assert_eq!(Image::from_file(fixture("bricks.ppm")).unwrap(), bricks());
+}
+
+#[test]
+fn plain_pgm_scales_samples() {
+    let image = Image::from_file(fixture("bricks.pgm")).unwrap();
+    let gray = |value: u8| Color{r: value, g: value, b: value};
+    // 4-bit samples: 15 is white, 6 is 6/15 of it
+    let expected: Vec<Color> = bricks().pixels.iter().map(|&color| if color == BRICK { gray(255) } else { gray(102) }).collect();
+    assert_eq!(image.pixels, expected);
+}
+
+#[test]
+fn malformed_ppm() {
+    assert!(matches!(Image::parse(b"P6\n6 6\n255\n\x01\x02"), Err(ImageError::Parse(_))));
+    assert!(matches!(Image::parse(b"P3\n1 1\n0\n0 0 0"), Err(ImageError::Parse(_))));
+    assert!(matches!(Image::parse(b"GIF89a"), Err(ImageError::Unsupported(_))));
+    
+    // Sizes that overflow, with one or two bytes per sample
+    let too_large = |header: &str| match Image::parse(header.as_bytes()) {
+        Err(ImageError::Parse(message)) => message == "image is too large",
+        _ => false,
+    };
+    assert!(too_large("P6\n4294967296 4294967296\n255\n"));
+    assert!(too_large("P6\n4294967296 1073741824\n65535\n"));
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn png() {
+    // Filtered scanlines in fixed and dynamic Huffman blocks, and a palette
+    assert_eq!(Image::from_file(fixture("bricks.png")).unwrap(), bricks());
+    assert_eq!(Image::from_file(fixture("bricks_palette.png")).unwrap(), bricks());
+    
+    let gradient = Image::from_file(fixture("gradient.png")).unwrap();
+    for y in 0..32 {
+        for x in 0..32 {
+            let expected = Color{r: x as u8 * 8, g: y as u8 * 8, b: (x ^ y) as u8 * 8};
+            assert_eq!(gradient.get_pixel(x, y), Some(expected), "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn png_round_trip() {
+    let mut data = Vec::new();
+    bricks().write_png(&mut data).unwrap();
+    assert_eq!(Image::parse(&data).unwrap(), bricks());
+}
+
+#[cfg(not(feature = "png"))]
+#[test]
+fn png_needs_feature() {
+    assert!(matches!(Image::from_file(fixture("bricks.png")), Err(ImageError::Unsupported(_))));
+}
+
+#[test]
+fn pixel_mappings() {
+    let wrap = Point{x: 6.0, y: 6.0};
+    let background = Texture::from_file(fixture("bricks.ppm"), wrap, PixelMapping::Background).unwrap();
+    assert_eq!(background.get_cell(0.5, 1.5).bg_color, Some(BRICK));
+    assert_eq!(background.get_cell(0.5, 1.5).fg_color, None);
+    
+    let shaded = Texture::from_image(&bricks(), wrap, PixelMapping::Shaded(GlyphSet::Ascii));
+    assert_eq!(shaded.get_cell(0.5, 1.5).fg_color, Some(BRICK));
+    // The red bricks are lighter than the gray mortar, so they get a denser
+    // glyph
+    assert_eq!(shaded.get_cell(0.5, 1.5).character, Some('+'));
+    assert_eq!(shaded.get_cell(0.5, 0.5).character, Some('.'));
+}
+
+#[test]
+fn text_texture() {
+    let texture = Texture::from_text_file(fixture("bricks.texture")).unwrap();
+    assert_eq!((texture.width(), texture.height()), (6, 6));
+    assert_eq!((texture.wrap.x, texture.wrap.y), (6.0, 6.0));
+    
+    let colors: Vec<Color> = texture.cells().iter().map(|cell| cell.bg_color.unwrap()).collect();
+    assert_eq!(colors, bricks().pixels);
+    let brick = texture.get_cell(0.5, 1.5);
+    assert_eq!((brick.character, brick.fg_color), (Some(' '), Some(Color{r: 255, g: 255, b: 255})));
+    let mortar = texture.get_cell(2.5, 4.5);
+    assert_eq!((mortar.character, mortar.fg_color), (Some('|'), None));
+}
+
+#[test]
+fn text_texture_keyed_by_glyph() {
+    let texture = Texture::parse("transparent .\nlegend\n# fg #0f0\nglyphs\n|#.|\n|.#|\n").unwrap();
+    assert_eq!((texture.wrap.x, texture.wrap.y), (1.0, 1.0));
+    assert_eq!(texture.cells()[0].character, Some('#'));
+    assert_eq!(texture.cells()[0].fg_color, Some(Color{r: 0, g: 255, b: 0}));
+    assert_eq!(texture.cells()[1].character, None);
+    assert_eq!(texture.cells()[1].fg_color, None);
+}
+
+#[test]
+fn text_texture_errors() {
+    let error_at = |text: &str| match Texture::parse(text) {
+        Err(TextureError::Parse{line, column, ..}) => (line, column),
+        other => panic!("expected a parse error, got {:?}", other),
+    };
+    
+    assert_eq!(error_at("glyphs\n|ab|\n|abc|"), (3, 1));
+    assert_eq!(error_at("glyphs\n  |ab"), (2, 6));
+    assert_eq!(error_at("legend\na fg #12345\n"), (2, 6));
+    assert_eq!(error_at("legend\na fg #123 xy #456\n"), (2, 11));
+    assert_eq!(error_at("wrap 6\nglyphs\n|a|"), (1, 1));
+    assert_eq!(error_at("glyphs\n|ab|\ncolors\n|a |"), (4, 2));
+    assert_eq!(error_at("legend\n"), (1, 1));
+    
+    let error = Texture::parse("glyphs\n|\u{4e00}|").unwrap_err();
+    assert_eq!(error.to_string(), "line 2, column 2: '\u{4e00}' is not a single column character");
+}
+
+#[test]
+fn empty_textures() {
+    let wrap = Point{x: 1.0, y: 1.0};
+    let empty = TextureCell::default();
+    for (width, height) in [(0, 0), (3, 0), (0, 3)] {
+        let generated = Texture::from_generator(width, height, wrap, &|_, _| TextureCell{character: Some('#'), fg_color: None, bg_color: None});
+        assert_eq!(*generated.get_cell(0.5, 0.5), empty);
+        let image = Texture::from_image(&Image::new(width, height, BRICK), wrap, PixelMapping::Background);
+        assert_eq!(*image.get_cell(0.25, 0.75), empty);
+        assert!(Texture::from_cells(width, height, wrap, Vec::new()).is_none());
+    }
+}
+
+#[test]
+fn images_with_missing_pixels() {
+    let wrap = Point{x: 2.0, y: 2.0};
+    let mut image = Image::new(2, 2, BRICK);
+    image.pixels.truncate(3);
+    let texture = Texture::from_image(&image, wrap, PixelMapping::Background);
+    assert_eq!(texture.cells().len(), 4);
+    assert_eq!(texture.get_cell(0.5, 1.5).bg_color, Some(BRICK));
+    assert_eq!(*texture.get_cell(1.5, 1.5), TextureCell::default());
+    
+    image.pixels.resize(10, MORTAR);
+    let texture = Texture::from_image(&image, wrap, PixelMapping::Background);
+    assert_eq!(texture.cells().len(), 4);
+    assert_eq!(texture.get_cell(1.5, 1.5).bg_color, Some(MORTAR));
+}